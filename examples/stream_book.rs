@@ -0,0 +1,52 @@
+// Keep a symbol's order book (SUIUSDT by default) in sync from the diff depth stream
+// and print the top 5 levels after every update, using only the library's public API.
+//
+//     cargo run --example stream_book [SYMBOL]
+
+use binance_price_checker::ws::{DepthEvent, LocalBook};
+use binance_price_checker::{endpoint, proxy, BinanceClient};
+use futures_util::StreamExt;
+use std::error::Error;
+use tokio_tungstenite::tungstenite::Message;
+
+const LEVELS: u32 = 5;
+const SNAPSHOT_LIMIT: u32 = 1000;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let symbol = std::env::args().nth(1).unwrap_or("SUIUSDT".to_string());
+    let rest = endpoint::Endpoint::Com;
+    let stream_url = format!(
+        "{}/ws/{}@depth",
+        endpoint::stream_base(&rest).ok_or("no stream host")?,
+        symbol.to_lowercase()
+    );
+
+    // Subscribe first so no event between the snapshot and the first read is lost
+    let (mut stream, _) = proxy::connect_websocket(&stream_url, None).await?;
+    let api = BinanceClient::new(reqwest::Client::new(), rest.url());
+    let (snapshot, _) = api.get_orderbook_snapshot(&symbol, SNAPSHOT_LIMIT).await?;
+    let mut book = LocalBook::from_snapshot(snapshot);
+
+    while let Some(message) = stream.next().await {
+        let Message::Text(text) = message? else {
+            continue;
+        };
+        let event: DepthEvent = serde_json::from_str(&text)?;
+        // A gap means an event went missing; ws::spawn recovers from that by starting
+        // over with a new snapshot, this just stops
+        if !book.apply(event)? {
+            continue;
+        }
+
+        let top = book.to_orderbook(LEVELS);
+        println!("{} at update {}", symbol, book.last_update_id());
+        for (bid, ask) in top.bids.iter().zip(&top.asks) {
+            println!(
+                "  {:>12} {:>14}  |  {:<12} {:<14}",
+                bid.price, bid.qty, ask.price, ask.qty
+            );
+        }
+    }
+    Ok(())
+}
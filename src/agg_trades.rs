@@ -0,0 +1,242 @@
+// Trade tape capture: subscribes to `<symbol>@aggTrade` for each symbol and appends
+// every aggregated trade as one JSON line to `aggtrades_<SYMBOL>.ndjson`, the
+// counterpart to the depth snapshots main.rs records. A dropped connection is
+// retried with backoff; trades missed while disconnected show up as a gap in
+// tradeId and are logged. Buffered lines are flushed on Ctrl+C.
+
+use binance_price_checker::{jittered, logging, normalize_symbol, proxy, AggTrade};
+use futures_util::StreamExt;
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, info, warn};
+
+use binance_price_checker::endpoint::{self, Endpoint};
+use binance_price_checker::keyboard::{self, Controls};
+use binance_price_checker::ndjson::NdjsonWriter;
+
+const USAGE: &str = "Usage: agg-trades [--symbol SYMBOL[,SYMBOL...]] [--output-dir DIR] \
+                     [--base-url com|us|https://HOST] [--stream-url WSS_URL] [--compress] \
+                     [--proxy URL]";
+const SYMBOL: &str = "SUIUSDT";
+const OUTPUT_DIR: &str = "./trade_snapshots";
+const RECONNECT_BASE_DELAY: f64 = 0.5; // Seconds, doubling after each failed attempt
+const RECONNECT_MAX_DELAY: f64 = 30.0;
+// Longest the loop waits for a message before flushing and checking for Ctrl+C
+const IDLE_TICK: Duration = Duration::from_millis(250);
+
+// Combined-stream envelope: {"stream":"suiusdt@aggTrade","data":{...}}
+#[derive(Deserialize)]
+struct StreamEvent {
+    data: AggTrade,
+}
+
+struct Settings {
+    symbols: Vec<String>,
+    output_dir: String,
+    stream_base: String,
+    compress: bool,
+    proxy: Option<Url>,
+}
+
+fn parse_args() -> Result<Settings, Box<dyn Error>> {
+    let mut symbols = vec![SYMBOL.to_string()];
+    let mut output_dir = OUTPUT_DIR.to_string();
+    let mut stream_url = None;
+    let mut compress = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--symbol" => {
+                let value = args.next().ok_or(USAGE)?;
+                symbols = value
+                    .split(',')
+                    .filter(|s| !s.trim().is_empty())
+                    .map(normalize_symbol)
+                    .collect::<Result<_, _>>()?;
+            }
+            "--output-dir" => output_dir = args.next().ok_or(USAGE)?,
+            "--stream-url" => stream_url = Some(args.next().ok_or(USAGE)?),
+            "--compress" => compress = true,
+            // Read by endpoint::from_args, logging::format_from_args and proxy::from_args
+            "--base-url" | "--log-format" | "--proxy" => {
+                args.next();
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, USAGE).into()),
+        }
+    }
+    if symbols.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    let stream_base = match stream_url {
+        Some(url) if url.starts_with("wss://") => url.trim_end_matches('/').to_string(),
+        Some(url) => return Err(format!("--stream-url '{}' is not a wss:// URL", url).into()),
+        None => {
+            let endpoint = endpoint::from_args(Endpoint::Com)?;
+            endpoint::stream_base(&endpoint)
+                .ok_or("A custom --base-url needs --stream-url")?
+                .to_string()
+        }
+    };
+    Ok(Settings {
+        symbols,
+        output_dir,
+        stream_base,
+        compress,
+        proxy: proxy::from_args()?,
+    })
+}
+
+// One file per symbol, plus the last tradeId written to it
+struct Tape {
+    writer: NdjsonWriter,
+    last_trade_id: Option<u64>,
+    trades: u64,
+}
+
+impl Tape {
+    fn record(&mut self, trade: &AggTrade) -> Result<(), Box<dyn Error>> {
+        if let Some(last) = self.last_trade_id {
+            if trade.trade_id > last + 1 {
+                warn!(
+                    "{}: missed {} aggregated trades ({}..{})",
+                    trade.symbol,
+                    trade.trade_id - last - 1,
+                    last + 1,
+                    trade.trade_id - 1
+                );
+            }
+        }
+        self.writer.append(&serde_json::to_vec(trade)?)?;
+        self.last_trade_id = Some(trade.trade_id);
+        self.trades += 1;
+        Ok(())
+    }
+}
+
+// Record trades until the stream ends, fails or Ctrl+C is pressed
+async fn follow_stream(
+    stream_url: &str,
+    proxy: Option<&Url>,
+    tapes: &mut HashMap<String, Tape>,
+    controls: &Controls,
+) -> Result<(), String> {
+    let (mut stream, _) = proxy::connect_websocket(stream_url, proxy)
+        .await
+        .map_err(|e| format!("connecting to {}: {}", stream_url, e))?;
+    info!("Connected to {}", stream_url);
+
+    while !controls.is_shutting_down() {
+        let message = tokio::select! {
+            message = stream.next() => message,
+            _ = controls.wait(IDLE_TICK) => {
+                flush_due(tapes);
+                continue;
+            }
+        };
+        let text = match message {
+            Some(message) => match message.map_err(|e| e.to_string())? {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                _ => continue,
+            },
+            None => break,
+        };
+        let event: StreamEvent =
+            serde_json::from_str(&text).map_err(|e| format!("unparseable event: {}", e))?;
+        if let Some(tape) = tapes.get_mut(&event.data.symbol) {
+            if let Err(e) = tape.record(&event.data) {
+                error!("Error writing {} trade: {}", event.data.symbol, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn flush_due(tapes: &mut HashMap<String, Tape>) {
+    for (symbol, tape) in tapes {
+        if let Err(e) = tape.writer.flush_if_due() {
+            error!("Error flushing {} trades: {}", symbol, e);
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    logging::init(logging::format_from_args()?, false);
+    let settings = parse_args()?;
+
+    let mut tapes = HashMap::with_capacity(settings.symbols.len());
+    for symbol in &settings.symbols {
+        let writer = NdjsonWriter::open(
+            &settings.output_dir,
+            &format!("aggtrades_{}", symbol),
+            None,
+            settings.compress,
+        )?;
+        tapes.insert(
+            symbol.clone(),
+            Tape {
+                writer,
+                last_trade_id: None,
+                trades: 0,
+            },
+        );
+    }
+    let streams: Vec<String> = settings
+        .symbols
+        .iter()
+        .map(|symbol| format!("{}@aggTrade", symbol.to_lowercase()))
+        .collect();
+    let stream_url = format!(
+        "{}/stream?streams={}",
+        settings.stream_base,
+        streams.join("/")
+    );
+    info!(
+        "Recording aggregated trades for {} to {}/",
+        settings.symbols.join(", "),
+        settings.output_dir
+    );
+
+    let controls = Arc::new(Controls::default());
+    keyboard::watch_ctrl_c(controls.clone());
+    let mut backoff = RECONNECT_BASE_DELAY;
+
+    while !controls.is_shutting_down() {
+        let recorded =
+            |tapes: &HashMap<String, Tape>| tapes.values().map(|t| t.trades).sum::<u64>();
+        let before = recorded(&tapes);
+        let error = match follow_stream(&stream_url, settings.proxy.as_ref(), &mut tapes, &controls)
+            .await
+        {
+            Ok(()) if controls.is_shutting_down() => break,
+            Ok(()) => "trade stream closed".to_string(),
+            Err(e) => format!("trade stream: {}", e),
+        };
+        // A connection that delivered trades starts the backoff schedule over
+        if recorded(&tapes) > before {
+            backoff = RECONNECT_BASE_DELAY;
+        }
+        let delay = jittered(backoff);
+        warn!("{}, reconnecting in {:.1}s", error, delay.as_secs_f64());
+        flush_due(&mut tapes);
+        controls.wait(delay).await;
+        backoff = (backoff * 2.0).min(RECONNECT_MAX_DELAY);
+    }
+
+    for (symbol, tape) in tapes {
+        let trades = tape.trades;
+        match tape.writer.close() {
+            Ok(()) => info!("{}: recorded {} trades", symbol, trades),
+            Err(e) => error!("Error closing {} trades: {}", symbol, e),
+        }
+    }
+    Ok(())
+}
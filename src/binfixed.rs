@@ -0,0 +1,181 @@
+// Compact fixed-point snapshot encoding (`binfixed`).
+//
+// Binance sends prices and quantities as decimal strings with 8 fractional
+// digits. Each value is converted exactly to integer units of 1e-8, then
+// divided by the symbol's tick size (prices) or step size (quantities), so a
+// level is two small integers instead of two strings.
+//
+// Layout, all integers little-endian:
+//
+//   magic            4 bytes  b"BOBF"
+//   version          u8       FORMAT_VERSION
+//   symbol_len       u8
+//   symbol           symbol_len bytes, ASCII
+//   tick_units       u64      price tick size in 1e-8 units
+//   step_units       u64      quantity step size in 1e-8 units
+//   last_update_id   u64
+//   local_timestamp  u64      seconds since the Unix epoch
+//   price            u64      ticker price in ticks
+//   price_timestamp  u64      milliseconds since the Unix epoch
+//   bid_count        u32
+//   ask_count        u32
+//   levels           (bid_count + ask_count) x (price ticks u64, qty steps u64)
+//
+// Readers must reject versions they don't know; new fields are only ever
+// added behind a version bump.
+
+use std::error::Error;
+
+pub const FORMAT_VERSION: u8 = 1;
+const MAGIC: &[u8; 4] = b"BOBF";
+const UNITS_PER_WHOLE: u64 = 100_000_000;
+
+pub struct FixedBook {
+    pub symbol: String,
+    pub tick_units: u64,
+    pub step_units: u64,
+    pub last_update_id: u64,
+    pub local_timestamp: u64,
+    pub price: String,
+    pub price_timestamp: u64,
+    pub bids: Vec<[String; 2]>,
+    pub asks: Vec<[String; 2]>,
+}
+
+/// Parse a decimal string such as "1.23450000" into exact 1e-8 units.
+pub fn parse_units(value: &str) -> Result<u64, Box<dyn Error>> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > 8 {
+        return Err(format!("'{}' has more than 8 decimal places", value).into());
+    }
+    let whole: u64 = if whole.is_empty() { 0 } else { whole.parse()? };
+    let fraction: u64 = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<8}", fraction).parse()?
+    };
+    whole
+        .checked_mul(UNITS_PER_WHOLE)
+        .and_then(|units| units.checked_add(fraction))
+        .ok_or_else(|| format!("'{}' is too large", value).into())
+}
+
+fn format_units(units: u64) -> String {
+    format!("{}.{:08}", units / UNITS_PER_WHOLE, units % UNITS_PER_WHOLE)
+}
+
+fn to_scaled(value: &str, scale: u64) -> Result<u64, Box<dyn Error>> {
+    let units = parse_units(value)?;
+    if units % scale != 0 {
+        return Err(format!(
+            "'{}' is not a multiple of the {} increment",
+            value,
+            format_units(scale)
+        )
+        .into());
+    }
+    Ok(units / scale)
+}
+
+pub fn encode(book: &FixedBook) -> Result<Vec<u8>, Box<dyn Error>> {
+    if book.tick_units == 0 || book.step_units == 0 {
+        return Err("Tick and step size must be non-zero".into());
+    }
+    let symbol_len = u8::try_from(book.symbol.len())?;
+
+    let mut out = Vec::with_capacity(64 + 16 * (book.bids.len() + book.asks.len()));
+    out.extend_from_slice(MAGIC);
+    out.push(FORMAT_VERSION);
+    out.push(symbol_len);
+    out.extend_from_slice(book.symbol.as_bytes());
+    for value in [
+        book.tick_units,
+        book.step_units,
+        book.last_update_id,
+        book.local_timestamp,
+        to_scaled(&book.price, book.tick_units)?,
+        book.price_timestamp,
+    ] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&u32::try_from(book.bids.len())?.to_le_bytes());
+    out.extend_from_slice(&u32::try_from(book.asks.len())?.to_le_bytes());
+    for level in book.bids.iter().chain(&book.asks) {
+        out.extend_from_slice(&to_scaled(&level[0], book.tick_units)?.to_le_bytes());
+        out.extend_from_slice(&to_scaled(&level[1], book.step_units)?.to_le_bytes());
+    }
+    Ok(out)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], Box<dyn Error>> {
+        if self.bytes.len() < len {
+            return Err("Truncated binfixed snapshot".into());
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Box<dyn Error>> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<FixedBook, Box<dyn Error>> {
+    let mut reader = Reader { bytes };
+    if reader.take(4)? != MAGIC {
+        return Err("Not a binfixed snapshot (bad magic)".into());
+    }
+    let version = reader.u8()?;
+    if version != FORMAT_VERSION {
+        return Err(format!("Unsupported binfixed version {}", version).into());
+    }
+    let symbol_len = reader.u8()? as usize;
+    let symbol = String::from_utf8(reader.take(symbol_len)?.to_vec())?;
+    let tick_units = reader.u64()?;
+    let step_units = reader.u64()?;
+    let last_update_id = reader.u64()?;
+    let local_timestamp = reader.u64()?;
+    let price = format_units(reader.u64()? * tick_units);
+    let price_timestamp = reader.u64()?;
+    let bid_count = reader.u32()? as usize;
+    let ask_count = reader.u32()? as usize;
+
+    let mut read_levels = |count: usize| -> Result<Vec<[String; 2]>, Box<dyn Error>> {
+        (0..count)
+            .map(|_| {
+                let price = format_units(reader.u64()? * tick_units);
+                let qty = format_units(reader.u64()? * step_units);
+                Ok([price, qty])
+            })
+            .collect()
+    };
+    let bids = read_levels(bid_count)?;
+    let asks = read_levels(ask_count)?;
+
+    Ok(FixedBook {
+        symbol,
+        tick_units,
+        step_units,
+        last_update_id,
+        local_timestamp,
+        price,
+        price_timestamp,
+        bids,
+        asks,
+    })
+}
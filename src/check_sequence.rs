@@ -0,0 +1,159 @@
+use binance_price_checker::normalize_symbol;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::{error::Error, fs, path::PathBuf};
+
+const USAGE: &str = "Usage: check-sequence DIR [--symbol SYMBOL]";
+
+// Only the field this tool needs; everything else in the snapshot is ignored
+#[derive(Deserialize, Debug)]
+struct SequencedSnapshot {
+    sequence: Option<u64>,
+}
+
+// Symbol from an `orderbook_<SYMBOL>_<timestamp>_<sequence>.json` filename (symbols
+// never contain '_')
+fn symbol_from_filename(name: &str) -> Option<&str> {
+    let stem = name.strip_prefix("orderbook_")?.strip_suffix(".json")?;
+    stem.split_once('_').map(|(symbol, _timestamp)| symbol)
+}
+
+// Files in `dir` plus those in main.rs's `part_NNNNN` rotation subdirectories
+fn snapshot_paths(dir: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_part = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("part_"));
+        if is_part && path.is_dir() {
+            for part_entry in fs::read_dir(&path)? {
+                paths.push(part_entry?.path());
+            }
+        } else {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+// Report gaps and duplicates in one symbol's sorted sequence numbers
+fn check_symbol(symbol: &str, sequences: &BTreeMap<u64, Vec<String>>, unsequenced: usize) -> bool {
+    let mut ok = true;
+    let (Some(&first), Some(&last)) = (sequences.keys().next(), sequences.keys().next_back())
+    else {
+        println!("{}: no sequenced snapshots", symbol);
+        return unsequenced == 0;
+    };
+
+    let mut missing = 0;
+    let mut expected = first;
+    for (&sequence, files) in sequences {
+        if sequence > expected {
+            let gap_end = sequence - 1;
+            if gap_end == expected {
+                println!("{}: missing {}", symbol, expected);
+            } else {
+                println!(
+                    "{}: missing {}-{} ({} snapshots)",
+                    symbol,
+                    expected,
+                    gap_end,
+                    sequence - expected
+                );
+            }
+            missing += sequence - expected;
+            ok = false;
+        }
+        if files.len() > 1 {
+            println!("{}: duplicate {} in {}", symbol, sequence, files.join(", "));
+            ok = false;
+        }
+        expected = sequence + 1;
+    }
+
+    if unsequenced > 0 {
+        println!("{}: {} files have no sequence field", symbol, unsequenced);
+        ok = false;
+    }
+
+    println!(
+        "{}: sequences {}-{}, {} files, {} missing{}",
+        symbol,
+        first,
+        last,
+        sequences.values().map(Vec::len).sum::<usize>(),
+        missing,
+        if ok { ", OK" } else { "" }
+    );
+    ok
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let dir = args.next().ok_or(USAGE)?;
+    let only_symbol = match args.next().as_deref() {
+        Some("--symbol") => Some(normalize_symbol(&args.next().ok_or(USAGE)?)?),
+        Some(other) => return Err(format!("Unexpected argument '{}'\n{}", other, USAGE).into()),
+        None => None,
+    };
+
+    // symbol -> sequence -> files carrying that sequence
+    let mut by_symbol: BTreeMap<String, BTreeMap<u64, Vec<String>>> = BTreeMap::new();
+    let mut unsequenced: BTreeMap<String, usize> = BTreeMap::new();
+
+    for path in snapshot_paths(&dir)? {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some(symbol) = symbol_from_filename(name) else {
+            continue;
+        };
+        if only_symbol.as_deref().is_some_and(|only| only != symbol) {
+            continue;
+        }
+
+        let snapshot = match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                serde_json::from_str::<SequencedSnapshot>(&data).map_err(|e| e.to_string())
+            }) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                eprintln!("Skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        match snapshot.sequence {
+            Some(sequence) => by_symbol
+                .entry(symbol.to_string())
+                .or_default()
+                .entry(sequence)
+                .or_default()
+                .push(name.to_string()),
+            None => *unsequenced.entry(symbol.to_string()).or_default() += 1,
+        }
+    }
+
+    let mut symbols: Vec<&String> = by_symbol.keys().chain(unsequenced.keys()).collect();
+    symbols.sort();
+    symbols.dedup();
+    if symbols.is_empty() {
+        return Err(format!("No snapshot files found in {}", dir).into());
+    }
+
+    let empty = BTreeMap::new();
+    let mut all_ok = true;
+    for symbol in symbols {
+        let sequences = by_symbol.get(symbol).unwrap_or(&empty);
+        let unsequenced = unsequenced.get(symbol).copied().unwrap_or(0);
+        all_ok &= check_symbol(symbol, sequences, unsequenced);
+    }
+
+    if !all_ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
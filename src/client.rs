@@ -0,0 +1,649 @@
+// REST access to one Binance endpoint: request/response checks, retries and the
+// depth/price fetches every binary needs.
+
+use crate::{proxy, Kline, OrderBook, PriceData, Ticker24h};
+use reqwest::header::HeaderMap;
+use reqwest::{header, redirect, Client, Proxy, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::time::{sleep, Duration};
+use tracing::{debug, debug_span};
+
+pub const MAX_REDIRECTS: usize = 5; // Redirects to allow in the reqwest client before ApiError::Redirect
+pub const DEFAULT_TIMEOUT: f64 = 10.0; // Seconds a whole request may take, body included
+pub const DEFAULT_CONNECT_TIMEOUT: f64 = 5.0; // Seconds to establish the connection
+pub const KLINE_LIMIT: u32 = 1000; // Most candles /api/v3/klines returns per request
+const MAX_FETCH_ATTEMPTS: u32 = 4; // Tries per request on connection errors and 5xx
+const RETRY_BASE_DELAY: f64 = 0.1; // Seconds before the first retry, doubling after each one
+const RETRY_MAX_DELAY: f64 = 2.0; // Cap on the backoff delay
+
+// Share of each backoff delay that is randomized so many instances don't retry in lockstep;
+// 1.0 is full jitter (uniform in [0, delay]), 0.0 keeps the plain exponential schedule
+const BACKOFF_JITTER: f64 = 1.0;
+const USED_WEIGHT_HEADER: &str = "x-mbx-used-weight-1m";
+const NORMALIZE_SYMBOL_CASE: bool = true; // Uppercase symbols before use; REST rejects lowercase
+
+// Failures from Binance API requests, split out where the fix differs from "try again"
+#[derive(Debug)]
+pub enum ApiError {
+    // Redirect loop, too many hops, or a 3xx that couldn't be followed
+    Redirect {
+        url: String,
+        status: Option<StatusCode>,
+    },
+    // 451/403: the endpoint refuses requests from this location
+    RegionBlocked {
+        url: String,
+        status: StatusCode,
+    },
+    // No response within the client's timeout (or no connection within the connect timeout)
+    Timeout {
+        url: String,
+        connect: bool,
+    },
+    Http {
+        context: &'static str,
+        status: StatusCode,
+    },
+    // 429: over the request weight limit, 418: banned for ignoring 429s; Binance says
+    // how long to back off in Retry-After. Never retried in the request: the ban goes
+    // to the WeightTracker and callers hold off through throttle_delay
+    RateLimited {
+        context: &'static str,
+        retry_after: Option<Duration>,
+    },
+    // A successful response carrying Binance's error body instead of the data, e.g.
+    // -1121 "Invalid symbol."
+    Binance {
+        context: &'static str,
+        code: i64,
+        msg: String,
+    },
+    // A successful response whose body didn't deserialize
+    Parse {
+        context: &'static str,
+        error: serde_json::Error,
+        body: String,
+    },
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Redirect { url, status } => {
+                write!(f, "Request to {} was redirected", url)?;
+                if let Some(status) = status {
+                    write!(f, " ({})", status)?;
+                }
+                write!(
+                    f,
+                    " and could not be completed within {} hops; the endpoint may be blocked in your region",
+                    MAX_REDIRECTS
+                )
+            }
+            ApiError::RegionBlocked { url, status } => write!(
+                f,
+                "{} is unavailable from your region ({}). Use https://api.binance.us \
+                 (for US users) or route requests through a proxy",
+                url, status
+            ),
+            ApiError::Timeout { url, connect: true } => {
+                write!(f, "Timed out connecting to {}", url)
+            }
+            ApiError::Timeout { url, .. } => write!(f, "Request to {} timed out", url),
+            ApiError::Http { context, status } => write!(f, "API Error {}: {}", context, status),
+            ApiError::RateLimited {
+                context,
+                retry_after,
+            } => {
+                write!(f, "Rate limited {}", context)?;
+                if let Some(retry_after) = retry_after {
+                    write!(f, " (retry after {}s)", retry_after.as_secs())?;
+                }
+                Ok(())
+            }
+            ApiError::Binance { context, code, msg } => {
+                write!(f, "Binance error {} {}: {}", code, context, msg)
+            }
+            ApiError::Parse { context, error, .. } => {
+                write!(f, "Unparseable response {}: {}", context, error)
+            }
+        }
+    }
+}
+
+impl Error for ApiError {}
+
+/// The reqwest client every binary uses: at most MAX_REDIRECTS hops, and no request
+/// hangs past `timeout` (or `connect_timeout` while connecting). Requests go through
+/// `proxy` (see proxy::resolve), or directly, whatever the environment says.
+pub fn http_client(
+    timeout: Duration,
+    connect_timeout: Duration,
+    proxy: Option<&Url>,
+) -> reqwest::Result<Client> {
+    let builder = Client::builder()
+        .redirect(redirect::Policy::limited(MAX_REDIRECTS))
+        .timeout(timeout)
+        .connect_timeout(connect_timeout);
+    match proxy {
+        // reqwest sends the URL's user:pass as Proxy-Authorization or SOCKS5 auth
+        Some(proxy) => builder.proxy(Proxy::all(proxy.clone())?),
+        None => builder.no_proxy(),
+    }
+    .build()
+}
+
+/// Positive number of seconds, as taken by `--timeout` and `--connect-timeout`.
+pub fn parse_seconds(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(seconds),
+        Ok(_) => Err("must be a positive number of seconds".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// http_client with `--timeout SECS` / `--connect-timeout SECS` and the proxy from
+/// proxy::from_args, for binaries without a clap parser.
+pub fn http_client_from_args() -> Result<Client, Box<dyn Error>> {
+    let (mut timeout, mut connect_timeout) = (DEFAULT_TIMEOUT, DEFAULT_CONNECT_TIMEOUT);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let target = match arg.as_str() {
+            "--timeout" => &mut timeout,
+            "--connect-timeout" => &mut connect_timeout,
+            _ => continue,
+        };
+        let value = args.next().ok_or_else(|| format!("Usage: {} SECS", arg))?;
+        *target = parse_seconds(&value).map_err(|e| format!("{} '{}': {}", arg, value, e))?;
+    }
+    Ok(http_client(
+        Duration::from_secs_f64(timeout),
+        Duration::from_secs_f64(connect_timeout),
+        proxy::from_args()?.as_ref(),
+    )?)
+}
+
+/// A symbol as Binance expects it: trimmed and, with NORMALIZE_SYMBOL_CASE, uppercased.
+/// Err explains what is wrong with anything that isn't 1-20 ASCII letters and digits.
+pub fn normalize_symbol(symbol: &str) -> Result<String, String> {
+    let trimmed = symbol.trim();
+    let normalized = if NORMALIZE_SYMBOL_CASE {
+        trimmed.to_ascii_uppercase()
+    } else {
+        trimmed.to_string()
+    };
+
+    if normalized.is_empty() || normalized.len() > 20 {
+        return Err(format!(
+            "Invalid symbol '{}': expected 1-20 characters",
+            symbol
+        ));
+    }
+    if let Some(c) = normalized.chars().find(|c| !c.is_ascii_alphanumeric()) {
+        return Err(format!(
+            "Invalid symbol '{}': unexpected character '{}', use the pair without separators (e.g. SUIUSDT)",
+            symbol, c
+        ));
+    }
+    if normalized.chars().any(|c| c.is_ascii_lowercase()) {
+        return Err(format!(
+            "Invalid symbol '{}': Binance symbols are uppercase (try '{}')",
+            symbol,
+            normalized.to_ascii_uppercase()
+        ));
+    }
+
+    Ok(normalized)
+}
+
+/// `{base_url}{path}?symbol={symbol}{params}` with `symbol` normalized; `params` starts
+/// with `&` if non-empty.
+pub fn symbol_url(
+    base_url: &str,
+    path: &str,
+    symbol: &str,
+    params: &str,
+) -> Result<String, String> {
+    let symbol = normalize_symbol(symbol)?;
+    Ok(format!("{}{}?symbol={}{}", base_url, path, symbol, params))
+}
+
+/// Send a GET and turn non-success responses into an ApiError.
+pub async fn send_checked(
+    client: &Client,
+    url: &str,
+    context: &'static str,
+) -> Result<Response, Box<dyn Error>> {
+    send_request(client.get(url), url, context).await
+}
+
+/// send_checked for a prepared request; 304 passes through for conditional requests.
+pub async fn send_request(
+    request: RequestBuilder,
+    url: &str,
+    context: &'static str,
+) -> Result<Response, Box<dyn Error>> {
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) if e.is_redirect() => {
+            return Err(ApiError::Redirect {
+                url: url.to_string(),
+                status: e.status(),
+            }
+            .into())
+        }
+        Err(e) if e.is_timeout() => {
+            return Err(ApiError::Timeout {
+                url: url.to_string(),
+                connect: e.is_connect(),
+            }
+            .into())
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let status = response.status();
+    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+        return Ok(response);
+    }
+
+    let url = url.to_string();
+    Err(match status {
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::IM_A_TEAPOT => ApiError::RateLimited {
+            context,
+            retry_after: response
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_secs),
+        },
+        StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS | StatusCode::FORBIDDEN => {
+            ApiError::RegionBlocked { url, status }
+        }
+        _ if status.is_redirection() => ApiError::Redirect {
+            url,
+            status: Some(status),
+        },
+        _ => ApiError::Http { context, status },
+    }
+    .into())
+}
+
+/// A response body: the data, or the `{"code":-1121,"msg":"Invalid symbol."}` shape
+/// Binance sends instead, sometimes with a 200.
+#[derive(Debug)]
+pub enum BinanceResponse<T> {
+    Ok(T),
+    Error { code: i64, msg: String },
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: i64,
+    msg: String,
+}
+
+impl<T: DeserializeOwned> BinanceResponse<T> {
+    /// The error shape is checked first, so a T loose enough to accept anything (like
+    /// serde_json::Value) still sees errors. Otherwise T's own parse error is kept
+    /// as is.
+    pub fn from_body(body: &str) -> serde_json::Result<BinanceResponse<T>> {
+        if let Ok(ErrorBody { code, msg }) = serde_json::from_str(body) {
+            return Ok(BinanceResponse::Error { code, msg });
+        }
+        serde_json::from_str(body).map(BinanceResponse::Ok)
+    }
+}
+
+/// Deserialize a response body; Binance's error body becomes ApiError::Binance and
+/// any other failure keeps the body in ApiError::Parse.
+pub async fn parse_json<T: DeserializeOwned>(
+    response: Response,
+    context: &'static str,
+) -> Result<T, Box<dyn Error>> {
+    let body = response.text().await?;
+    let _parse = debug_span!("parse", context, bytes = body.len()).entered();
+    match BinanceResponse::from_body(&body) {
+        Ok(BinanceResponse::Ok(value)) => Ok(value),
+        Ok(BinanceResponse::Error { code, msg }) => {
+            Err(ApiError::Binance { context, code, msg }.into())
+        }
+        Err(error) => Err(ApiError::Parse {
+            context,
+            error,
+            body,
+        }
+        .into()),
+    }
+}
+
+/// `delay` seconds with its BACKOFF_JITTER share replaced by a uniformly random amount.
+pub fn jittered(delay: f64) -> Duration {
+    let jitter = delay * BACKOFF_JITTER.clamp(0.0, 1.0);
+    Duration::from_secs_f64(delay - jitter + rand::random::<f64>() * jitter)
+}
+
+// How long to wait before retrying `error`, or None if retrying won't help
+fn retry_delay(error: &(dyn Error + 'static), backoff: f64) -> Option<Duration> {
+    let backoff = jittered(backoff);
+    if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+        return (e.is_connect() || e.is_timeout()).then_some(backoff);
+    }
+    match error.downcast_ref::<ApiError>()? {
+        // The timeout itself was the wait; try again straight away
+        ApiError::Timeout { .. } => Some(Duration::ZERO),
+        ApiError::Http { status, .. } if status.is_server_error() => Some(backoff),
+        _ => None,
+    }
+}
+
+/// Request weight an endpoint reports in X-MBX-USED-WEIGHT-1M, and any ban from a
+/// 418/429, so callers can slow down before the limit is hit.
+#[derive(Default)]
+pub struct WeightTracker {
+    state: Mutex<WeightState>,
+}
+
+#[derive(Default)]
+struct WeightState {
+    used: u32,
+    minute: u64, // Unix minute the reading belongs to; Binance resets the count each minute
+    banned_until: Option<Instant>,
+}
+
+fn unix_minute() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 60)
+        .unwrap_or_default()
+}
+
+impl WeightTracker {
+    pub fn record(&self, headers: &HeaderMap) {
+        let used = headers
+            .get(USED_WEIGHT_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u32>().ok());
+        if let Some(used) = used {
+            debug!(used, "Request weight used this minute");
+            let mut state = self.state.lock().unwrap();
+            state.used = used;
+            state.minute = unix_minute();
+        }
+    }
+
+    pub fn record_ban(&self, retry_after: Duration) {
+        let until = Instant::now() + retry_after;
+        let mut state = self.state.lock().unwrap();
+        state.banned_until = Some(state.banned_until.map_or(until, |t| t.max(until)));
+    }
+
+    /// Weight used in the current minute, as of the last response.
+    pub fn used(&self) -> u32 {
+        let state = self.state.lock().unwrap();
+        if state.minute == unix_minute() {
+            state.used
+        } else {
+            0
+        }
+    }
+
+    /// How long to hold off: the rest of a ban, or the rest of the minute once the used
+    /// weight has reached `fraction` of `limit`. None when requests can go ahead.
+    pub fn throttle_delay(&self, limit: u32, fraction: f64) -> Option<Duration> {
+        let banned_until = self.state.lock().unwrap().banned_until;
+        if let Some(remaining) = banned_until.and_then(|t| t.checked_duration_since(Instant::now()))
+        {
+            return Some(remaining);
+        }
+        if (self.used() as f64) < limit as f64 * fraction {
+            return None;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+        Some(Duration::from_secs(60) - Duration::from_millis(now.as_millis() as u64 % 60_000))
+    }
+}
+
+/// Per-minute REQUEST_WEIGHT limit advertised in exchangeInfo's rateLimits, the
+/// limit WeightTracker readings are measured against.
+pub fn weight_limit(exchange_info: &serde_json::Value) -> Result<u32, String> {
+    exchange_info["rateLimits"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|l| {
+            l["rateLimitType"] == "REQUEST_WEIGHT"
+                && l["interval"] == "MINUTE"
+                && l["intervalNum"] == 1
+        })
+        .and_then(|l| l["limit"].as_u64())
+        .map(|limit| limit as u32)
+        .ok_or_else(|| "No per-minute REQUEST_WEIGHT limit in exchangeInfo".to_string())
+}
+
+/// Client for one Binance REST endpoint (e.g. `https://api.binance.com`).
+pub struct BinanceClient {
+    http: Client,
+    base_url: String,
+    conditional_depth: bool,
+    // Last ETag and book per depth URL, so unchanged books come back as 304
+    depth_cache: Mutex<HashMap<String, (String, OrderBook)>>,
+    on_retry: fn(String),
+    weight: WeightTracker,
+}
+
+impl BinanceClient {
+    pub fn new(http: Client, base_url: &str) -> BinanceClient {
+        BinanceClient {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            conditional_depth: false,
+            depth_cache: Mutex::new(HashMap::new()),
+            on_retry: |message| tracing::warn!("{}", message),
+            weight: WeightTracker::default(),
+        }
+    }
+
+    /// Send If-None-Match on depth requests when the endpoint supplies an ETag.
+    pub fn conditional_depth(mut self, enabled: bool) -> BinanceClient {
+        self.conditional_depth = enabled;
+        self
+    }
+
+    /// Where "retrying in ..." messages go; a tracing warning by default.
+    pub fn on_retry(mut self, report: fn(String)) -> BinanceClient {
+        self.on_retry = report;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn http(&self) -> &Client {
+        &self.http
+    }
+
+    /// Request weight this endpoint reported for the price and depth fetches.
+    pub fn weight(&self) -> &WeightTracker {
+        &self.weight
+    }
+
+    pub async fn get_current_price(&self, symbol: &str) -> Result<PriceData, Box<dyn Error>> {
+        let url = symbol_url(&self.base_url, "/api/v3/ticker/price", symbol, "")?;
+
+        let response = self
+            .with_retry(|| send_checked(&self.http, &url, "getting price"))
+            .await?;
+        self.weight.record(response.headers());
+
+        let price_data: serde_json::Value = parse_json(response, "getting price").await?;
+        let price = price_data["price"]
+            .as_str()
+            .ok_or("Failed to extract price")?
+            .to_string();
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+
+        Ok(PriceData { price, timestamp })
+    }
+
+    /// 24h high/low/volume/change; weight 2 per call, so it is opt-in where used.
+    pub async fn get_24h_ticker(&self, symbol: &str) -> Result<Ticker24h, Box<dyn Error>> {
+        let url = symbol_url(&self.base_url, "/api/v3/ticker/24hr", symbol, "")?;
+        let response = self
+            .with_retry(|| send_checked(&self.http, &url, "getting 24h ticker"))
+            .await?;
+        self.weight.record(response.headers());
+        parse_json(response, "getting 24h ticker").await
+    }
+
+    /// Up to `limit` (at most KLINE_LIMIT) `interval` candles, oldest first, opening in
+    /// [`start_time`, `end_time`] (ms) when given; without a start, the latest ones.
+    /// The last candle may still be open.
+    pub async fn get_klines(
+        &self,
+        symbol: &str,
+        interval: &str,
+        start_time: Option<u64>,
+        end_time: Option<u64>,
+        limit: u32,
+    ) -> Result<Vec<Kline>, Box<dyn Error>> {
+        let mut params = format!("&interval={}&limit={}", interval, limit.min(KLINE_LIMIT));
+        if let Some(start_time) = start_time {
+            params.push_str(&format!("&startTime={}", start_time));
+        }
+        if let Some(end_time) = end_time {
+            params.push_str(&format!("&endTime={}", end_time));
+        }
+        let url = symbol_url(&self.base_url, "/api/v3/klines", symbol, &params)?;
+        let response = self
+            .with_retry(|| send_checked(&self.http, &url, "getting klines"))
+            .await?;
+        self.weight.record(response.headers());
+        parse_json(response, "getting klines").await
+    }
+
+    /// /api/v3/exchangeInfo for `symbol`, or for every symbol (weight 20) without one.
+    pub async fn get_exchange_info(
+        &self,
+        symbol: Option<&str>,
+    ) -> Result<serde_json::Value, Box<dyn Error>> {
+        let url = match symbol {
+            Some(symbol) => symbol_url(&self.base_url, "/api/v3/exchangeInfo", symbol, "")?,
+            None => format!("{}/api/v3/exchangeInfo", self.base_url),
+        };
+        let response = self
+            .with_retry(|| send_checked(&self.http, &url, "getting exchange info"))
+            .await?;
+        self.weight.record(response.headers());
+        parse_json(response, "getting exchange info").await
+    }
+
+    /// Returns the book and whether it is the cached copy from a 304 response.
+    pub async fn get_orderbook_snapshot(
+        &self,
+        symbol: &str,
+        limit: u32,
+    ) -> Result<(OrderBook, bool), Box<dyn Error>> {
+        let url = symbol_url(
+            &self.base_url,
+            "/api/v3/depth",
+            symbol,
+            &format!("&limit={}", limit),
+        )?;
+
+        let response = self
+            .with_retry(|| {
+                let mut request = self.http.get(&url);
+                if self.conditional_depth {
+                    if let Some((etag, _)) = self.depth_cache.lock().unwrap().get(&url) {
+                        request = request.header(header::IF_NONE_MATCH, etag.as_str());
+                    }
+                }
+                send_request(request, &url, "getting orderbook")
+            })
+            .await?;
+        self.weight.record(response.headers());
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            let cached = self
+                .depth_cache
+                .lock()
+                .unwrap()
+                .get(&url)
+                .map(|(_, book)| book.clone());
+            return match cached {
+                Some(orderbook) => Ok((orderbook, true)),
+                None => Err("Depth endpoint returned 304 with no cached book".into()),
+            };
+        }
+
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let orderbook: OrderBook = parse_json(response, "getting orderbook").await?;
+
+        // No ETag means the endpoint doesn't support conditional requests; nothing to cache
+        if let (true, Some(etag)) = (self.conditional_depth, etag) {
+            self.depth_cache
+                .lock()
+                .unwrap()
+                .insert(url, (etag, orderbook.clone()));
+        }
+        Ok((orderbook, false))
+    }
+
+    // Run `send` up to MAX_FETCH_ATTEMPTS times, backing off exponentially after
+    // connection errors and 5xx responses. A 418/429 records its ban and is returned
+    // straight away, as is anything else and the last failure once attempts run out.
+    async fn with_retry<T, F, Fut>(&self, mut send: F) -> Result<T, Box<dyn Error>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Box<dyn Error>>>,
+    {
+        let mut backoff = RETRY_BASE_DELAY;
+        let mut attempt = 1;
+        loop {
+            // Scoped so the (non-Send) error is gone before sleeping, keeping the future Send
+            let delay = {
+                let error = match send().await {
+                    Ok(value) => return Ok(value),
+                    Err(e) => e,
+                };
+                if let Some(ApiError::RateLimited {
+                    retry_after: Some(retry_after),
+                    ..
+                }) = error.downcast_ref::<ApiError>()
+                {
+                    self.weight.record_ban(*retry_after);
+                }
+                let delay = match retry_delay(error.as_ref(), backoff) {
+                    Some(delay) if attempt < MAX_FETCH_ATTEMPTS => delay,
+                    _ => return Err(error),
+                };
+                (self.on_retry)(format!(
+                    "{}, retrying in {:.1}s ({}/{})",
+                    error,
+                    delay.as_secs_f64(),
+                    attempt,
+                    MAX_FETCH_ATTEMPTS - 1
+                ));
+                delay
+            };
+            sleep(delay).await;
+            backoff = (backoff * 2.0).min(RETRY_MAX_DELAY);
+            attempt += 1;
+        }
+    }
+}
@@ -0,0 +1,197 @@
+// `--config FILE`: capture settings from a TOML file. Top-level keys are the long
+// flags (`depth_limit` or `depth-limit`) and go through the same parsers as the
+// command line, which wins wherever both set a value. An `[overrides.<SYMBOL>]`
+// table changes depth_limit or interval for that symbol only:
+//
+//     symbol = ["BTCUSDT", "ETHUSDT"]
+//     interval = 0.5
+//     compress = true
+//
+//     [overrides.BTCUSDT]
+//     depth_limit = 500
+//     interval = 2.0
+//
+// `--print-config` prints the merged result in the same format, so it can be saved
+// and passed back with --config.
+
+use crate::{parse_depth_limit, parse_interval, Args};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+
+// Command-line only: they decide how the file is read, not what it sets
+const NOT_IN_FILE: &[&str] = &["config", "print_config", "help", "version"];
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolOverride {
+    pub depth_limit: Option<u32>,
+    pub interval: Option<f64>,
+}
+
+// What --print-config needs besides Args: where each value came from
+pub struct Effective {
+    matches: ArgMatches,
+    from_file: BTreeSet<String>, // Arg ids set by the file and not overridden
+    overrides: BTreeMap<String, SymbolOverride>,
+}
+
+/// Parse the command line, filling in anything it leaves unset from `--config`.
+pub fn load() -> Result<(Args, Effective), Box<dyn Error>> {
+    let cli: Vec<OsString> = std::env::args_os().collect();
+    let cli_matches = Args::command().get_matches_from(&cli);
+    let Some(path) = cli_matches.get_one::<String>("config") else {
+        let args = Args::from_arg_matches(&cli_matches)?;
+        let effective = Effective {
+            matches: cli_matches,
+            from_file: BTreeSet::new(),
+            overrides: BTreeMap::new(),
+        };
+        return Ok((args, effective));
+    };
+
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let mut table: toml::Table =
+        toml::from_str(&contents).map_err(|e| format!("Invalid config {}: {}", path, e))?;
+    let overrides = match table.remove("overrides") {
+        Some(value) => parse_overrides(value).map_err(|e| format!("{}: {}", path, e))?,
+        None => BTreeMap::new(),
+    };
+
+    // File values become flags ahead of the real ones, except where the command line
+    // already sets the same arg
+    let command = Args::command();
+    let mut argv = vec![cli[0].clone()];
+    let mut from_file = BTreeSet::new();
+    for (key, value) in table {
+        let id = key.replace('-', "_");
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id.as_str())
+            .filter(|_| !NOT_IN_FILE.contains(&id.as_str()))
+            .ok_or_else(|| format!("{}: unknown setting '{}'", path, key))?;
+        if cli_matches.value_source(&id) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let flag = format!("--{}", arg.get_long().unwrap_or(&id));
+        let takes_values = arg.get_action().takes_values();
+        let values = match value {
+            toml::Value::Boolean(set) if !takes_values => {
+                if set {
+                    argv.push(flag.into());
+                }
+                from_file.insert(id);
+                continue;
+            }
+            toml::Value::Array(items) if matches!(arg.get_action(), ArgAction::Append) => items,
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                toml::Value::String(s) if takes_values => s,
+                toml::Value::Integer(_) | toml::Value::Float(_) if takes_values => {
+                    value.to_string()
+                }
+                value => return Err(format!("{}: '{}' can't be {}", path, key, value).into()),
+            };
+            argv.push(format!("{}={}", flag, value).into());
+        }
+        from_file.insert(id);
+    }
+    argv.extend(cli.into_iter().skip(1));
+
+    let matches = command.get_matches_from(argv);
+    let args = Args::from_arg_matches(&matches)?;
+    let effective = Effective {
+        matches,
+        from_file,
+        overrides,
+    };
+    Ok((args, effective))
+}
+
+// `[overrides.<SYMBOL>]` tables, checked like the matching flags
+fn parse_overrides(value: toml::Value) -> Result<BTreeMap<String, SymbolOverride>, String> {
+    let overrides: BTreeMap<String, SymbolOverride> = value
+        .try_into()
+        .map_err(|e| format!("invalid [overrides]: {}", e))?;
+    for (symbol, setting) in &overrides {
+        if let Some(limit) = setting.depth_limit {
+            parse_depth_limit(&limit.to_string())
+                .map_err(|e| format!("overrides.{}.depth_limit {}", symbol, e))?;
+        }
+        if let Some(interval) = setting.interval {
+            parse_interval(&interval.to_string())
+                .map_err(|e| format!("overrides.{}.interval {}", symbol, e))?;
+        }
+    }
+    Ok(overrides)
+}
+
+impl Effective {
+    pub fn overrides(&self) -> &BTreeMap<String, SymbolOverride> {
+        &self.overrides
+    }
+
+    /// Every setting in --config syntax, each commented with where its value came from.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for arg in Args::command().get_arguments() {
+            let id = arg.get_id().as_str();
+            if NOT_IN_FILE.contains(&id) {
+                continue;
+            }
+            let source = if self.from_file.contains(id) {
+                "config file"
+            } else {
+                match self.matches.value_source(id) {
+                    Some(ValueSource::CommandLine) => "command line",
+                    Some(ValueSource::EnvVariable) => "environment",
+                    _ => "default",
+                }
+            };
+            let Some(raw) = self.matches.get_raw(id) else {
+                out.push_str(&format!("# {} is not set\n", id));
+                continue;
+            };
+            let mut values: Vec<toml::Value> = raw
+                .map(|value| typed(&value.to_string_lossy(), arg.get_action()))
+                .collect();
+            let value = if matches!(arg.get_action(), ArgAction::Append) {
+                toml::Value::Array(values)
+            } else {
+                values.pop().unwrap_or(toml::Value::Boolean(false))
+            };
+            out.push_str(&format!("{} = {} # {}\n", id, value, source));
+        }
+        for (symbol, setting) in &self.overrides {
+            out.push_str(&format!("\n[overrides.{}]\n", symbol));
+            if let Some(limit) = setting.depth_limit {
+                out.push_str(&format!("depth_limit = {}\n", limit));
+            }
+            if let Some(interval) = setting.interval {
+                out.push_str(&format!("interval = {}\n", toml::Value::Float(interval)));
+            }
+        }
+        out
+    }
+}
+
+// Booleans for flags, numbers where the value reads as one, strings otherwise
+fn typed(raw: &str, action: &ArgAction) -> toml::Value {
+    if !action.takes_values() {
+        return toml::Value::Boolean(raw == "true");
+    }
+    if let Ok(n) = raw.parse::<i64>() {
+        return toml::Value::Integer(n);
+    }
+    match raw.parse::<f64>() {
+        Ok(n) if n.is_finite() => toml::Value::Float(n),
+        _ => toml::Value::String(raw.to_string()),
+    }
+}
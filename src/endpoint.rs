@@ -0,0 +1,84 @@
+// Which Binance REST host a binary talks to. `--base-url com`, `--base-url us`
+// or a full https URL such as `--base-url https://api1.binance.com`.
+
+use std::fmt;
+use std::str::FromStr;
+
+const COM_URL: &str = "https://api.binance.com";
+const US_URL: &str = "https://api.binance.us";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Endpoint {
+    Com,
+    Us,
+    Custom(String),
+}
+
+impl Endpoint {
+    /// Base URL without a trailing slash, ready for `format!("{}/api/v3/...")`.
+    pub fn url(&self) -> &str {
+        match self {
+            Endpoint::Com => COM_URL,
+            Endpoint::Us => US_URL,
+            Endpoint::Custom(url) => url,
+        }
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Endpoint, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "com" => return Ok(Endpoint::Com),
+            "us" => return Ok(Endpoint::Us),
+            _ => {}
+        }
+
+        let url = value.trim_end_matches('/');
+        let host = url
+            .strip_prefix("https://")
+            .ok_or_else(|| format!("'{}' is not an https:// URL (or 'com' / 'us')", value))?;
+        // Only a host (and optional port) is expected; the API paths are appended later
+        let valid_host = !host.is_empty()
+            && host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':'));
+        if !valid_host {
+            return Err(format!("'{}' is not a valid base URL host", host));
+        }
+
+        Ok(match url {
+            COM_URL => Endpoint::Com,
+            US_URL => Endpoint::Us,
+            _ => Endpoint::Custom(url.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.url())
+    }
+}
+
+/// WebSocket stream host matching a known REST endpoint; custom ones have none.
+pub fn stream_base(endpoint: &Endpoint) -> Option<&'static str> {
+    match endpoint {
+        Endpoint::Com => Some("wss://stream.binance.com:9443"),
+        Endpoint::Us => Some("wss://stream.binance.us:9443"),
+        Endpoint::Custom(_) => None,
+    }
+}
+
+/// Endpoint from a `--base-url VALUE` argument, for binaries without a clap parser.
+pub fn from_args(default: Endpoint) -> Result<Endpoint, String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--base-url" {
+            let value = args.next().ok_or("Usage: --base-url com|us|https://HOST")?;
+            return value.parse();
+        }
+    }
+    Ok(default)
+}
@@ -0,0 +1,161 @@
+// Interactive key controls for the capture loop: 'p' toggles pause, 's' forces
+// an immediate snapshot. Ctrl+C asks the loop to shut down cleanly.
+//
+// The terminal is put in cbreak mode (no line buffering, no echo) rather than
+// full raw mode, so println! output keeps its newlines and Ctrl+C still works.
+
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tokio::time::{sleep, Duration};
+use tracing::info;
+
+#[derive(Default)]
+pub struct Controls {
+    paused: AtomicBool,
+    force_snapshot: AtomicBool,
+    shutdown: AtomicBool,
+    wake: Notify,
+}
+
+impl Controls {
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Returns true once per 's' keypress.
+    pub fn take_force_snapshot(&self) -> bool {
+        self.force_snapshot.swap(false, Ordering::SeqCst)
+    }
+
+    /// True once Ctrl+C has been pressed or shut_down called.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// Ask the loop to stop the way the first Ctrl+C does.
+    pub fn shut_down(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.wake.notify_one();
+    }
+
+    /// Sleep for `duration`, returning early if a key changed the loop state.
+    pub async fn wait(&self, duration: Duration) {
+        tokio::select! {
+            _ = sleep(duration) => {}
+            _ = self.wake.notified() => {}
+        }
+    }
+
+    fn handle_key(&self, key: u8) {
+        match key {
+            b'p' | b'P' => {
+                let paused = !self.paused.fetch_xor(true, Ordering::SeqCst);
+                info!(
+                    "{}",
+                    if paused {
+                        "Capture paused (press 'p' to resume)"
+                    } else {
+                        "Capture resumed"
+                    }
+                );
+            }
+            b's' | b'S' => {
+                info!("Forcing snapshot");
+                self.force_snapshot.store(true, Ordering::SeqCst);
+            }
+            _ => return,
+        }
+        self.wake.notify_one();
+    }
+}
+
+/// Restores the original terminal mode when dropped.
+pub struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Request a clean shutdown on the first Ctrl+C; a second one exits immediately.
+pub fn watch_ctrl_c(controls: Arc<Controls>) {
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            info!("Shutting down after this iteration (Ctrl+C again to quit now)");
+            controls.shut_down();
+        }
+        // Exiting skips destructors, so restore the terminal explicitly
+        if tokio::signal::ctrl_c().await.is_ok() {
+            restore_terminal();
+            std::process::exit(130);
+        }
+    });
+}
+
+/// Switch the terminal to cbreak mode and start reading keys for `controls`.
+pub fn spawn(controls: Arc<Controls>) -> io::Result<TerminalGuard> {
+    enable_cbreak()?;
+
+    // Blocking stdin reads live on their own thread so they never stall the runtime
+    std::thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut key = [0u8; 1];
+        while let Ok(1) = stdin.read(&mut key) {
+            controls.handle_key(key[0]);
+        }
+    });
+
+    info!("Interactive mode: 'p' pause/resume, 's' snapshot now, Ctrl+C quit");
+    Ok(TerminalGuard)
+}
+
+#[cfg(unix)]
+static ORIGINAL_TERMIOS: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+#[cfg(unix)]
+fn enable_cbreak() -> io::Result<()> {
+    // SAFETY: tcgetattr/tcsetattr only read and write the termios struct we pass in
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if let Ok(mut original) = ORIGINAL_TERMIOS.lock() {
+            original.get_or_insert(termios);
+        }
+
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn restore_terminal() {
+    if let Ok(original) = ORIGINAL_TERMIOS.lock() {
+        if let Some(termios) = original.as_ref() {
+            // SAFETY: restoring the termios captured by enable_cbreak
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn enable_cbreak() -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "interactive mode is only supported on Unix terminals",
+    ))
+}
+
+#[cfg(not(unix))]
+fn restore_terminal() {}
@@ -0,0 +1,442 @@
+// Candle capture for backtesting: fetches closed `--interval` candles for a symbol
+// from /api/v3/klines and appends them to `klines_<SYMBOL>_<interval>.csv` (or
+// `.ndjson`) in the output directory.
+//
+// The backfill pages forward from the start with startTime, KLINE_LIMIT candles per
+// request. It starts after the last candle already in the file, else at --start,
+// else --limit candles back from now. Candles are only written once closed and never
+// twice, so a rerun picks up where the last one stopped. With --follow it keeps
+// polling, once each candle closes, until Ctrl+C.
+
+use binance_price_checker::{
+    http_client_from_args, logging, normalize_symbol, ApiError, BinanceClient, Kline, KLINE_LIMIT,
+};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use binance_price_checker::endpoint::{self, Endpoint};
+use binance_price_checker::keyboard::{self, Controls};
+use binance_price_checker::ndjson::NdjsonWriter;
+
+const USAGE: &str = "Usage: klines [--symbol SYMBOL] [--interval 1m] [--limit N] \
+                     [--start TIME] [--end TIME] [--format csv|ndjson] [--output-dir DIR] \
+                     [--follow] [--base-url com|us|https://HOST] [--proxy URL]\n\
+                     TIME is YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS in UTC, or Unix ms";
+const SYMBOL: &str = "SUIUSDT";
+const INTERVAL: &str = "1m";
+const OUTPUT_DIR: &str = "./kline_snapshots";
+const DEFAULT_BACKFILL: u64 = KLINE_LIMIT as u64; // Candles fetched on a first run without --start
+const DEFAULT_WEIGHT_LIMIT: u32 = 1200;
+const WEIGHT_THROTTLE_FRACTION: f64 = 0.9; // Hold off until the next minute past this share
+const CLOSE_GRACE: Duration = Duration::from_secs(2); // Wait after a close before asking for it
+const RATE_LIMIT_DELAY: Duration = Duration::from_secs(5); // After a 429 without Retry-After
+const FOLLOW_RETRY_DELAY: Duration = Duration::from_secs(10); // After a failed poll in --follow
+
+// Binance's intervals with their length in ms; 1M counts as 31 days, which only
+// matters for the --limit starting point
+const INTERVALS: [(&str, u64); 16] = [
+    ("1s", 1_000),
+    ("1m", 60_000),
+    ("3m", 180_000),
+    ("5m", 300_000),
+    ("15m", 900_000),
+    ("30m", 1_800_000),
+    ("1h", 3_600_000),
+    ("2h", 7_200_000),
+    ("4h", 14_400_000),
+    ("6h", 21_600_000),
+    ("8h", 28_800_000),
+    ("12h", 43_200_000),
+    ("1d", 86_400_000),
+    ("3d", 259_200_000),
+    ("1w", 604_800_000),
+    ("1M", 2_678_400_000),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Csv,
+    Ndjson,
+}
+
+struct Settings {
+    symbol: String,
+    interval: &'static str,
+    interval_ms: u64,
+    backfill: u64,
+    start: Option<u64>,
+    end: Option<u64>,
+    format: Format,
+    output_dir: String,
+    follow: bool,
+}
+
+fn parse_args() -> Result<Settings, Box<dyn Error>> {
+    let mut symbol = SYMBOL.to_string();
+    let mut interval = INTERVAL.to_string();
+    let mut backfill = DEFAULT_BACKFILL;
+    let (mut start, mut end) = (None, None);
+    let mut format = Format::Csv;
+    let mut output_dir = OUTPUT_DIR.to_string();
+    let mut follow = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--symbol" => symbol = normalize_symbol(&args.next().ok_or(USAGE)?)?,
+            "--interval" => interval = args.next().ok_or(USAGE)?,
+            "--limit" => {
+                let value = args.next().ok_or(USAGE)?;
+                backfill = match value.parse::<u64>() {
+                    Ok(n) if n > 0 => n,
+                    _ => return Err(format!("--limit '{}' is not a positive count", value).into()),
+                };
+            }
+            "--start" => start = Some(parse_time(&args.next().ok_or(USAGE)?)?),
+            "--end" => end = Some(parse_time(&args.next().ok_or(USAGE)?)?),
+            "--format" => {
+                format = match args.next().ok_or(USAGE)?.as_str() {
+                    "csv" => Format::Csv,
+                    "ndjson" => Format::Ndjson,
+                    other => {
+                        return Err(format!("--format '{}' is not csv or ndjson", other).into())
+                    }
+                }
+            }
+            "--output-dir" => output_dir = args.next().ok_or(USAGE)?,
+            "--follow" => follow = true,
+            // Read by endpoint::from_args, logging::format_from_args and
+            // http_client_from_args
+            "--base-url" | "--log-format" | "--timeout" | "--connect-timeout" | "--proxy" => {
+                args.next();
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, USAGE).into()),
+        }
+    }
+    if symbol.is_empty() {
+        return Err(USAGE.into());
+    }
+    let Some(&(interval, interval_ms)) = INTERVALS.iter().find(|(name, _)| *name == interval)
+    else {
+        let names: Vec<&str> = INTERVALS.iter().map(|(name, _)| *name).collect();
+        return Err(format!(
+            "--interval '{}' is not one of {}",
+            interval,
+            names.join(", ")
+        )
+        .into());
+    };
+    if let (Some(start), Some(end)) = (start, end) {
+        if end < start {
+            return Err("--end is before --start".into());
+        }
+    }
+    if follow && end.is_some() {
+        return Err("--follow can't be combined with --end".into());
+    }
+    Ok(Settings {
+        symbol,
+        interval,
+        interval_ms,
+        backfill,
+        start,
+        end,
+        format,
+        output_dir,
+        follow,
+    })
+}
+
+// `YYYY-MM-DD`, `YYYY-MM-DDTHH:MM:SS` (UTC) or Unix ms
+fn parse_time(value: &str) -> Result<u64, String> {
+    if let Ok(ms) = value.parse::<u64>() {
+        return Ok(ms);
+    }
+    let time = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .map_err(|_| format!("'{}' is not YYYY-MM-DD[THH:MM:SS] or Unix ms", value))?;
+    u64::try_from(time.and_utc().timestamp_millis())
+        .map_err(|_| format!("'{}' is before 1970", value))
+}
+
+fn now_ms() -> u64 {
+    Utc::now().timestamp_millis() as u64
+}
+
+enum Sink {
+    Csv(csv::Writer<File>),
+    Ndjson(NdjsonWriter),
+}
+
+// The candle file, and the open time of the last candle in it for deduplication
+struct Output {
+    path: String,
+    sink: Sink,
+    last_open_time: Option<u64>,
+    written: u64,
+}
+
+impl Output {
+    fn open(settings: &Settings) -> Result<Output, Box<dyn Error>> {
+        let name = format!("klines_{}_{}", settings.symbol, settings.interval);
+        let extension = match settings.format {
+            Format::Csv => "csv",
+            Format::Ndjson => "ndjson",
+        };
+        let path = format!("{}/{}.{}", settings.output_dir, name, extension);
+        // An empty file (even without its CSV header) has nothing to continue from
+        let has_data = std::fs::metadata(&path).is_ok_and(|meta| meta.len() > 0);
+        let last_open_time = if has_data {
+            last_open_time(&path, settings.format)
+                .map_err(|e| format!("reading {}: {}", path, e))?
+        } else {
+            None
+        };
+
+        let sink = match settings.format {
+            Format::Csv => {
+                std::fs::create_dir_all(&settings.output_dir)?;
+                let file = OpenOptions::new().create(true).append(true).open(&path)?;
+                // The header goes at the top of a new file only
+                let new_file = file.metadata()?.len() == 0;
+                Sink::Csv(
+                    csv::WriterBuilder::new()
+                        .has_headers(new_file)
+                        .from_writer(file),
+                )
+            }
+            Format::Ndjson => Sink::Ndjson(NdjsonWriter::open(
+                &settings.output_dir,
+                &name,
+                None,
+                false,
+            )?),
+        };
+        Ok(Output {
+            path,
+            sink,
+            last_open_time,
+            written: 0,
+        })
+    }
+
+    // Where the next request starts: just past the last candle written
+    fn next_start(&self) -> Option<u64> {
+        self.last_open_time.map(|t| t + 1)
+    }
+
+    // Append `kline` unless the file already has it; returns whether it was written
+    fn write(&mut self, kline: &Kline) -> Result<bool, Box<dyn Error>> {
+        if self
+            .last_open_time
+            .is_some_and(|last| kline.open_time <= last)
+        {
+            return Ok(false);
+        }
+        match &mut self.sink {
+            Sink::Csv(writer) => writer.serialize(kline)?,
+            Sink::Ndjson(writer) => {
+                writer.append(&serde_json::to_vec(kline)?)?;
+            }
+        }
+        self.last_open_time = Some(kline.open_time);
+        self.written += 1;
+        Ok(true)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Csv(writer) => writer.flush(),
+            Sink::Ndjson(writer) => writer.flush(),
+        }
+    }
+
+    fn close(self) -> io::Result<()> {
+        match self.sink {
+            Sink::Csv(mut writer) => writer.flush(),
+            Sink::Ndjson(writer) => writer.close(),
+        }
+    }
+}
+
+// openTime of the last candle in an existing file
+fn last_open_time(path: &str, format: Format) -> Result<Option<u64>, Box<dyn Error>> {
+    let mut last = None;
+    match format {
+        Format::Csv => {
+            let mut reader = csv::Reader::from_path(path)?;
+            let column = reader
+                .headers()?
+                .iter()
+                .position(|name| name == "openTime")
+                .ok_or("no openTime column")?;
+            for record in reader.records() {
+                if let Some(field) = record?.get(column) {
+                    last = Some(field.parse::<u64>()?);
+                }
+            }
+        }
+        Format::Ndjson => {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let candle: serde_json::Value = serde_json::from_str(&line)?;
+                last = Some(candle["openTime"].as_u64().ok_or("line without openTime")?);
+            }
+        }
+    }
+    Ok(last)
+}
+
+// Page forward from `start` until caught up with the candle still open (returned by
+// its close time) or with --end
+async fn catch_up(
+    api: &BinanceClient,
+    settings: &Settings,
+    output: &mut Output,
+    mut start: u64,
+    controls: &Controls,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    while !controls.is_shutting_down() {
+        if let Some(delay) = api
+            .weight()
+            .throttle_delay(DEFAULT_WEIGHT_LIMIT, WEIGHT_THROTTLE_FRACTION)
+        {
+            warn!(
+                "Request weight nearly used up, pausing {:.0}s",
+                delay.as_secs_f64()
+            );
+            controls.wait(delay).await;
+            continue;
+        }
+
+        let klines = match api
+            .get_klines(
+                &settings.symbol,
+                settings.interval,
+                Some(start),
+                settings.end,
+                KLINE_LIMIT,
+            )
+            .await
+        {
+            Ok(klines) => klines,
+            // The ban is recorded; throttle_delay waits it out on the next pass
+            Err(e) if matches!(e.downcast_ref(), Some(ApiError::RateLimited { .. })) => {
+                warn!("{}", e);
+                controls.wait(RATE_LIMIT_DELAY).await;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let full_page = klines.len() == KLINE_LIMIT as usize;
+        let now = now_ms();
+        let mut written = 0;
+        for kline in &klines {
+            if kline.close_time >= now {
+                output.flush()?;
+                return Ok(Some(kline.close_time));
+            }
+            written += output.write(kline)? as u64;
+        }
+        output.flush()?;
+        if written > 0 {
+            info!(
+                "{} {}: {} candles up to {}",
+                settings.symbol,
+                settings.interval,
+                written,
+                output.last_open_time.map_or("-".to_string(), format_time)
+            );
+        }
+        match klines.last() {
+            Some(last) if full_page => start = last.open_time + 1,
+            _ => break,
+        }
+    }
+    Ok(None)
+}
+
+fn format_time(ms: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms as i64)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| ms.to_string())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    logging::init(logging::format_from_args()?, false);
+    let settings = parse_args()?;
+    let endpoint = endpoint::from_args(Endpoint::Com)?;
+    let api = BinanceClient::new(http_client_from_args()?, endpoint.url());
+    let mut output = Output::open(&settings)?;
+
+    let mut start = match (output.next_start(), settings.start) {
+        (Some(next), _) => {
+            info!("Continuing {} after its last candle", output.path);
+            next
+        }
+        (None, Some(start)) => start,
+        // One extra interval back, so the open candle doesn't use up one of the count
+        (None, None) => now_ms().saturating_sub((settings.backfill + 1) * settings.interval_ms),
+    };
+    info!(
+        "Fetching {} {} candles from {} into {}",
+        settings.symbol,
+        settings.interval,
+        format_time(start),
+        output.path
+    );
+
+    let controls = Arc::new(Controls::default());
+    keyboard::watch_ctrl_c(controls.clone());
+    while !controls.is_shutting_down() {
+        let result = catch_up(&api, &settings, &mut output, start, &controls).await;
+        if !settings.follow {
+            if let Err(e) = result {
+                let _ = output.close();
+                return Err(e);
+            }
+            break;
+        }
+        let delay = match result {
+            Ok(open_close_time) => {
+                // Ask again once the open candle has closed
+                let next_close = open_close_time
+                    .unwrap_or_else(|| now_ms() + settings.interval_ms)
+                    .saturating_sub(now_ms());
+                Duration::from_millis(next_close + 1) + CLOSE_GRACE
+            }
+            Err(e) => {
+                error!(
+                    "Error polling {} candles: {}, retrying in {}s",
+                    settings.symbol,
+                    e,
+                    FOLLOW_RETRY_DELAY.as_secs()
+                );
+                FOLLOW_RETRY_DELAY
+            }
+        };
+        if let Some(next) = output.next_start() {
+            start = next;
+        }
+        controls.wait(delay).await;
+    }
+
+    let written = output.written;
+    let path = output.path.clone();
+    output.close()?;
+    info!("Wrote {} candles to {}", written, path);
+    Ok(())
+}
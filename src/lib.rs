@@ -0,0 +1,682 @@
+// Types and REST client shared by the binaries in src/, so parsing and
+// formatting fixes happen in one place.
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+pub mod binfixed;
+mod client;
+pub mod endpoint;
+pub mod keyboard;
+pub mod logging;
+pub mod ndjson;
+pub mod proxy;
+pub mod table;
+pub mod ws;
+
+pub use client::{
+    http_client, http_client_from_args, jittered, normalize_symbol, parse_json, parse_seconds,
+    send_checked, send_request, symbol_url, weight_limit, ApiError, BinanceClient, BinanceResponse,
+    WeightTracker, DEFAULT_CONNECT_TIMEOUT, DEFAULT_TIMEOUT, KLINE_LIMIT, MAX_REDIRECTS,
+};
+
+/// ANSI codes for terminal output, picked at runtime: [`ansi::palette`] is all empty
+/// strings with `--no-color`, a non-empty NO_COLOR, or stdout not being a terminal.
+pub mod ansi {
+    use std::io::IsTerminal;
+    use std::sync::OnceLock;
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct Palette {
+        pub red: &'static str,
+        pub green: &'static str,
+        pub yellow: &'static str,
+        pub bold: &'static str,
+        pub dim: &'static str,
+        pub reset: &'static str,
+        pub clear: &'static str, // Clear the screen and move to the top left
+    }
+
+    pub const COLOR: Palette = Palette {
+        red: "\x1b[31m",
+        green: "\x1b[32m",
+        yellow: "\x1b[33m",
+        bold: "\x1b[1m",
+        dim: "\x1b[2m",
+        reset: "\x1b[0m",
+        clear: "\x1b[2J\x1b[H",
+    };
+
+    pub const PLAIN: Palette = Palette {
+        red: "",
+        green: "",
+        yellow: "",
+        bold: "",
+        dim: "",
+        reset: "",
+        clear: "",
+    };
+
+    static PALETTE: OnceLock<Palette> = OnceLock::new();
+
+    impl Palette {
+        pub fn is_plain(&self) -> bool {
+            self.reset.is_empty()
+        }
+    }
+
+    /// This process's palette, decided on first use.
+    pub fn palette() -> Palette {
+        *PALETTE.get_or_init(|| {
+            if turned_off() || !std::io::stdout().is_terminal() {
+                PLAIN
+            } else {
+                COLOR
+            }
+        })
+    }
+
+    /// `--no-color` was passed or NO_COLOR is set to anything but the empty string
+    /// (https://no-color.org).
+    pub fn turned_off() -> bool {
+        std::env::args().any(|arg| arg == "--no-color")
+            || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+    }
+}
+
+/// Response of /api/v3/depth: bids high to low, asks low to high.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OrderBook {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+/// One book level. Binance sends `["0.12340000", "1523.10000000"]`; parsing into
+/// Decimal keeps every digit, and writing back gives the same strings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level {
+    pub price: Decimal,
+    pub qty: Decimal,
+}
+
+impl Level {
+    /// `[price, qty]` in the exact string form Binance sent.
+    pub fn to_strings(&self) -> [String; 2] {
+        [self.price.to_string(), self.qty.to_string()]
+    }
+
+    /// Price as f64 for derived statistics; not for anything that is stored as a level.
+    pub fn price_f64(&self) -> f64 {
+        self.price.to_f64().unwrap_or(f64::NAN)
+    }
+
+    pub fn qty_f64(&self) -> f64 {
+        self.qty.to_f64().unwrap_or(f64::NAN)
+    }
+}
+
+impl Serialize for Level {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_strings().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Level {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Level, D::Error> {
+        let [price, qty] = <[String; 2]>::deserialize(deserializer)?;
+        let parse = |value: &str| {
+            Decimal::from_str(value)
+                .map_err(|e| D::Error::custom(format!("invalid decimal '{}': {}", value, e)))
+        };
+        Ok(Level {
+            price: parse(&price)?,
+            qty: parse(&qty)?,
+        })
+    }
+}
+
+/// A depth response that breaks an invariant Binance guarantees. Levels are
+/// numbered from 0 at the top of their side.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    NonPositive {
+        side: Side,
+        index: usize,
+        level: Level,
+    },
+    BidsNotDescending {
+        index: usize,
+        level: Level,
+        previous: Level,
+    },
+    AsksNotAscending {
+        index: usize,
+        level: Level,
+        previous: Level,
+    },
+    Crossed {
+        best_bid: Level,
+        best_ask: Level,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::NonPositive { side, index, level } => write!(
+                f,
+                "{} level {} is not positive: {} x {}",
+                side.name(),
+                index,
+                level.price,
+                level.qty
+            ),
+            ValidationError::BidsNotDescending {
+                index,
+                level,
+                previous,
+            } => write!(
+                f,
+                "bids not descending at level {}: {} after {}",
+                index, level.price, previous.price
+            ),
+            ValidationError::AsksNotAscending {
+                index,
+                level,
+                previous,
+            } => write!(
+                f,
+                "asks not ascending at level {}: {} after {}",
+                index, level.price, previous.price
+            ),
+            ValidationError::Crossed { best_bid, best_ask } => write!(
+                f,
+                "crossed book: best bid {} >= best ask {}",
+                best_bid.price, best_ask.price
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Check every price and quantity is positive, bids are strictly descending, asks
+/// strictly ascending and the book isn't crossed. An empty side passes.
+pub fn validate_orderbook(book: &OrderBook) -> Result<(), ValidationError> {
+    for (side, levels) in [(Side::Bid, &book.bids), (Side::Ask, &book.asks)] {
+        if let Some((index, level)) = levels
+            .iter()
+            .enumerate()
+            .find(|(_, level)| level.price <= Decimal::ZERO || level.qty <= Decimal::ZERO)
+        {
+            return Err(ValidationError::NonPositive {
+                side,
+                index,
+                level: *level,
+            });
+        }
+    }
+    for (index, pair) in book.bids.windows(2).enumerate() {
+        if pair[1].price >= pair[0].price {
+            return Err(ValidationError::BidsNotDescending {
+                index: index + 1,
+                level: pair[1],
+                previous: pair[0],
+            });
+        }
+    }
+    for (index, pair) in book.asks.windows(2).enumerate() {
+        if pair[1].price <= pair[0].price {
+            return Err(ValidationError::AsksNotAscending {
+                index: index + 1,
+                level: pair[1],
+                previous: pair[0],
+            });
+        }
+    }
+    if let (Some(best_bid), Some(best_ask)) = (book.bids.first(), book.asks.first()) {
+        if best_bid.price >= best_ask.price {
+            return Err(ValidationError::Crossed {
+                best_bid: *best_bid,
+                best_ask: *best_ask,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Book side to walk: `Ask` is the price of buying, `Bid` of selling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+impl Side {
+    pub fn name(self) -> &'static str {
+        match self {
+            Side::Bid => "bid",
+            Side::Ask => "ask",
+        }
+    }
+}
+
+/// Average fill price of a market order against one side of a book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VwapResult {
+    pub avg_price: Decimal,
+    pub filled_qty: Decimal,
+    /// False when the book ran out before the requested quantity was filled
+    pub complete: bool,
+}
+
+/// Walk `side` from the top until `qty` is filled. A book too thin for the whole
+/// quantity gives the partial fill with `complete: false`; None if nothing fills.
+pub fn vwap_for_quantity(book: &OrderBook, side: Side, qty: Decimal) -> Option<VwapResult> {
+    let levels = match side {
+        Side::Bid => &book.bids,
+        Side::Ask => &book.asks,
+    };
+    let (mut filled, mut notional) = (Decimal::ZERO, Decimal::ZERO);
+    for level in levels {
+        if filled >= qty {
+            break;
+        }
+        let take = level.qty.min(qty - filled);
+        filled += take;
+        notional += take * level.price;
+    }
+    if filled <= Decimal::ZERO {
+        return None;
+    }
+    Some(VwapResult {
+        avg_price: notional / filled,
+        filled_qty: filled,
+        complete: filled >= qty,
+    })
+}
+
+/// A level with the running totals of its side from the top of the book down to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CumulativeLevel {
+    pub level: Level,
+    pub cum_qty: Decimal,
+    /// Sum of price × qty over the same levels, in the quote asset
+    pub cum_notional: Decimal,
+}
+
+/// `side`'s levels in book order with cumulative quantity and notional. Summed as
+/// Decimal, so deep books add up exactly.
+pub fn with_cumulative(book: &OrderBook, side: Side) -> Vec<CumulativeLevel> {
+    let levels = match side {
+        Side::Bid => &book.bids,
+        Side::Ask => &book.asks,
+    };
+    let (mut cum_qty, mut cum_notional) = (Decimal::ZERO, Decimal::ZERO);
+    levels
+        .iter()
+        .map(|level| {
+            cum_qty += level.qty;
+            cum_notional += level.price * level.qty;
+            CumulativeLevel {
+                level: *level,
+                cum_qty,
+                cum_notional,
+            }
+        })
+        .collect()
+}
+
+/// Bid share of the quantity in the top `levels` of each side, in [0, 1]; 0.5
+/// (neutral) when both sides are empty. Shallower books just use what they have.
+pub fn depth_imbalance(book: &OrderBook, levels: usize) -> f64 {
+    let side_qty = |side: &[Level]| -> Decimal { side.iter().take(levels).map(|l| l.qty).sum() };
+    let bid_qty = side_qty(&book.bids);
+    let total = bid_qty + side_qty(&book.asks);
+    if total.is_zero() {
+        return 0.5;
+    }
+    (bid_qty / total).to_f64().unwrap_or(0.5)
+}
+
+/// Mid of the best bid and ask; None when either side is empty.
+pub fn mid_price(book: &OrderBook) -> Option<Decimal> {
+    match (book.bids.first(), book.asks.first()) {
+        (Some(bid), Some(ask)) => Some((bid.price + ask.price) / Decimal::TWO),
+        _ => None,
+    }
+}
+
+/// Best ask minus best bid, negative when the book is crossed; None when either side
+/// is empty.
+pub fn spread(book: &OrderBook) -> Option<Decimal> {
+    match (book.bids.first(), book.asks.first()) {
+        (Some(bid), Some(ask)) => Some(ask.price - bid.price),
+        _ => None,
+    }
+}
+
+/// The spread in basis points of the mid price.
+pub fn spread_bps(book: &OrderBook) -> Option<f64> {
+    let ratio = spread(book)?.checked_div(mid_price(book)?)?;
+    (ratio * Decimal::from(10_000)).to_f64()
+}
+
+/// What each level contributes to book_imbalance.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Weight {
+    /// Quantity in the base asset
+    Base,
+    /// Quote notional, price × quantity
+    Quote,
+}
+
+/// (bid - ask) / total over every level of the book, in [-1, 1], summing each level's
+/// quantity or notional depending on `weight`; None for an empty book.
+pub fn book_imbalance(book: &OrderBook, weight: Weight) -> Option<f64> {
+    let side_total = |levels: &[Level]| -> f64 {
+        levels
+            .iter()
+            .map(|level| match weight {
+                Weight::Base => level.qty_f64(),
+                Weight::Quote => level.price_f64() * level.qty_f64(),
+            })
+            .sum()
+    };
+    let bid_total = side_total(&book.bids);
+    let ask_total = side_total(&book.asks);
+    let total = bid_total + ask_total;
+    (total != 0.0).then(|| (bid_total - ask_total) / total)
+}
+
+/// Quantity on `side` priced within `distance_bps` of mid; None when either side is
+/// empty.
+pub fn liquidity_within(book: &OrderBook, side: Side, distance_bps: f64) -> Option<f64> {
+    let mid = mid_price(book)?.to_f64()?;
+    let total = match side {
+        Side::Bid => {
+            let bound = mid * (1.0 - distance_bps / 10_000.0);
+            let inside = book.bids.iter().filter(|level| level.price_f64() >= bound);
+            inside.map(Level::qty_f64).sum()
+        }
+        Side::Ask => {
+            let bound = mid * (1.0 + distance_bps / 10_000.0);
+            let inside = book.asks.iter().filter(|level| level.price_f64() <= bound);
+            inside.map(Level::qty_f64).sum()
+        }
+    };
+    Some(total)
+}
+
+/// Fixed-length quantity profile around mid; bin 0 is nearest mid on both sides.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PriceBins {
+    pub span_bps: f64,
+    pub bids: Vec<f64>,
+    pub asks: Vec<f64>,
+}
+
+/// Sum quantity into `count` equal-width bins spanning `span_bps` from mid on each
+/// side. Levels beyond the span are dropped and bins the book doesn't reach stay
+/// zero; None when either side is empty.
+pub fn bin_by_distance(book: &OrderBook, count: usize, span_bps: f64) -> Option<PriceBins> {
+    let mid = mid_price(book)?.to_f64()?;
+    let bin_width = span_bps / count as f64;
+
+    let fill = |levels: &[Level]| -> Vec<f64> {
+        let mut bins = vec![0.0; count];
+        for level in levels {
+            let distance_bps = (level.price_f64() - mid).abs() / mid * 10_000.0;
+            if let Some(bin) = bins.get_mut((distance_bps / bin_width) as usize) {
+                *bin += level.qty_f64();
+            }
+        }
+        bins
+    };
+
+    Some(PriceBins {
+        span_bps,
+        bids: fill(&book.bids),
+        asks: fill(&book.asks),
+    })
+}
+
+/// Features of the book truncated to its best few levels per side.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Layer {
+    /// Levels per side actually available, at most the layer depth
+    pub levels: usize,
+    /// Quantity-weighted mean ask minus mean bid; None when either side is empty
+    pub vwap_spread: Option<f64>,
+    /// book_imbalance of the layer; None when it is empty
+    pub imbalance: Option<f64>,
+}
+
+/// Spread and imbalance over the best `depth` levels per side.
+pub fn book_layer(book: &OrderBook, depth: usize, weight: Weight) -> Layer {
+    let layer = OrderBook {
+        last_update_id: book.last_update_id,
+        bids: book.bids.iter().take(depth).cloned().collect(),
+        asks: book.asks.iter().take(depth).cloned().collect(),
+    };
+    let vwap = |levels: &[Level]| -> Option<f64> {
+        let (mut notional, mut qty) = (0.0, 0.0);
+        for level in levels {
+            notional += level.price_f64() * level.qty_f64();
+            qty += level.qty_f64();
+        }
+        (qty > 0.0).then(|| notional / qty)
+    };
+    let vwap_spread = match (vwap(&layer.asks), vwap(&layer.bids)) {
+        (Some(ask), Some(bid)) => Some(ask - bid),
+        _ => None,
+    };
+    Layer {
+        levels: layer.bids.len().max(layer.asks.len()),
+        vwap_spread,
+        imbalance: book_imbalance(&layer, weight),
+    }
+}
+
+/// What changed on one side between two books. Prices are the keys: a level is
+/// `added` if the price is new, `changed` if its quantity moved, and `removed` if it
+/// is gone or now has zero quantity (Binance's way of saying removed). `added` and
+/// `changed` are in the current book's order, `removed` in the previous one's.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SideDelta {
+    pub added: Vec<Level>,
+    pub changed: Vec<Level>,
+    pub removed: Vec<Decimal>,
+}
+
+impl SideDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct OrderBookDelta {
+    pub bids: SideDelta,
+    pub asks: SideDelta,
+}
+
+/// Level changes that turn `prev` into `curr`, per side.
+pub fn diff_orderbooks(prev: &OrderBook, curr: &OrderBook) -> OrderBookDelta {
+    OrderBookDelta {
+        bids: diff_side(&prev.bids, &curr.bids),
+        asks: diff_side(&prev.asks, &curr.asks),
+    }
+}
+
+fn diff_side(prev: &[Level], curr: &[Level]) -> SideDelta {
+    // Zero-quantity levels count as absent in either book
+    let live = |levels: &[Level]| -> HashMap<Decimal, Decimal> {
+        levels
+            .iter()
+            .filter(|level| !level.qty.is_zero())
+            .map(|level| (level.price, level.qty))
+            .collect()
+    };
+    let before = live(prev);
+    let after = live(curr);
+
+    let mut delta = SideDelta::default();
+    for level in curr.iter().filter(|level| !level.qty.is_zero()) {
+        match before.get(&level.price) {
+            None => delta.added.push(*level),
+            Some(qty) if *qty != level.qty => delta.changed.push(*level),
+            Some(_) => {}
+        }
+    }
+    for level in prev.iter().filter(|level| !level.qty.is_zero()) {
+        if !after.contains_key(&level.price) {
+            delta.removed.push(level.price);
+        }
+    }
+    delta
+}
+
+/// Rolling 24h statistics from /api/v3/ticker/24hr, under Binance's field names.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Ticker24h {
+    pub price_change_percent: Decimal,
+    pub high_price: Decimal,
+    pub low_price: Decimal,
+    pub volume: Decimal,       // Base asset
+    pub quote_volume: Decimal, // Quote asset
+}
+
+/// One `<symbol>@aggTrade` stream event. Reads the stream's one-letter keys and
+/// writes the descriptive names.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AggTrade {
+    #[serde(rename(deserialize = "s"))]
+    pub symbol: String,
+    #[serde(rename(deserialize = "a", serialize = "tradeId"))]
+    pub trade_id: u64,
+    #[serde(rename(deserialize = "p"))]
+    pub price: Decimal,
+    #[serde(rename(deserialize = "q"))]
+    pub qty: Decimal,
+    #[serde(rename(deserialize = "T", serialize = "tradeTime"))]
+    pub trade_time: u64, // ms
+    #[serde(rename(deserialize = "m", serialize = "isBuyerMaker"))]
+    pub is_buyer_maker: bool, // The buyer's order was resting, so the taker sold
+}
+
+/// One /api/v3/klines candle. Binance sends each as an array; it is written back
+/// with descriptive names.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "KlineRow", rename_all(serialize = "camelCase"))]
+pub struct Kline {
+    pub open_time: u64, // ms
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: Decimal,
+    pub close_time: u64, // ms, the last millisecond the candle covers
+    pub quote_volume: Decimal,
+    pub trades: u64,
+    pub taker_buy_volume: Decimal,
+    pub taker_buy_quote_volume: Decimal,
+}
+
+// [openTime, open, high, low, close, volume, closeTime, quoteVolume, trades,
+//  takerBuyVolume, takerBuyQuoteVolume, unused]
+#[derive(Deserialize)]
+struct KlineRow(
+    u64,
+    Decimal,
+    Decimal,
+    Decimal,
+    Decimal,
+    Decimal,
+    u64,
+    Decimal,
+    u64,
+    Decimal,
+    Decimal,
+    serde::de::IgnoredAny,
+);
+
+impl From<KlineRow> for Kline {
+    fn from(row: KlineRow) -> Kline {
+        Kline {
+            open_time: row.0,
+            open: row.1,
+            high: row.2,
+            low: row.3,
+            close: row.4,
+            volume: row.5,
+            close_time: row.6,
+            quote_volume: row.7,
+            trades: row.8,
+            taker_buy_volume: row.9,
+            taker_buy_quote_volume: row.10,
+        }
+    }
+}
+
+/// Last traded price from /api/v3/ticker/price, stamped with local receive time (ms).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PriceData {
+    pub price: String,
+    pub timestamp: u64,
+}
+
+/// A symbol's PRICE_FILTER tickSize and LOT_SIZE stepSize from /api/v3/exchangeInfo.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SymbolFilters {
+    pub tick_size: Decimal,
+    pub step_size: Decimal,
+}
+
+impl SymbolFilters {
+    /// `symbol`'s filters from an exchangeInfo response, for one symbol or all.
+    pub fn parse(exchange_info: &serde_json::Value, symbol: &str) -> Result<SymbolFilters, String> {
+        let filters = exchange_info["symbols"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|entry| entry["symbol"] == symbol)
+            .and_then(|entry| entry["filters"].as_array())
+            .ok_or_else(|| format!("No filters for {} in exchangeInfo", symbol))?;
+        let size = |filter_type: &str, field: &str| -> Result<Decimal, String> {
+            let value = filters
+                .iter()
+                .find(|f| f["filterType"] == filter_type)
+                .and_then(|f| f[field].as_str())
+                .ok_or_else(|| format!("Missing {} {} for {}", filter_type, field, symbol))?;
+            Decimal::from_str(value).map_err(|e| {
+                format!(
+                    "{} {} '{}' for {}: {}",
+                    filter_type, field, value, symbol, e
+                )
+            })
+        };
+        Ok(SymbolFilters {
+            tick_size: size("PRICE_FILTER", "tickSize")?,
+            step_size: size("LOT_SIZE", "stepSize")?,
+        })
+    }
+
+    /// Decimals worth showing in a price, e.g. tick "0.00010000" -> 4, "1.00000000" -> 0.
+    pub fn price_decimals(&self) -> usize {
+        self.tick_size.normalize().scale() as usize
+    }
+
+    /// Decimals worth showing in a quantity, from the step size.
+    pub fn qty_decimals(&self) -> usize {
+        self.step_size.normalize().scale() as usize
+    }
+}
@@ -0,0 +1,46 @@
+// Log setup shared by the binaries. Messages go through tracing to stderr, filtered
+// by RUST_LOG (default `info`), as readable lines or with `--log-format json` one
+// JSON object per event. Rendered books and reports stay on stdout.
+
+use crate::ansi;
+use std::io::{self, IsTerminal};
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    Human,
+    Json,
+}
+
+/// Format from a `--log-format VALUE` argument, for binaries without a clap parser.
+pub fn format_from_args() -> Result<LogFormat, String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--log-format" {
+            let value = args.next().ok_or("Usage: --log-format human|json")?;
+            return clap::ValueEnum::from_str(&value, true)
+                .map_err(|_| format!("Unknown --log-format '{}' (human or json)", value));
+        }
+    }
+    Ok(LogFormat::Human)
+}
+
+/// Install the global subscriber. With `span_timings`, every span also logs its
+/// busy/idle time when it closes.
+pub fn init(format: LogFormat, span_timings: bool) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let span_events = if span_timings {
+        FmtSpan::CLOSE
+    } else {
+        FmtSpan::NONE
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(span_events)
+        .with_ansi(io::stderr().is_terminal() && !ansi::turned_off())
+        .with_writer(io::stderr);
+    match format {
+        LogFormat::Human => builder.with_target(false).init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
@@ -1,188 +1,3272 @@
-use chrono::Local;
-use reqwest::{self, Client};
+use binance_price_checker::endpoint::{self, Endpoint};
+use binance_price_checker::logging::{self, LogFormat};
+use binance_price_checker::{
+    bin_by_distance, book_imbalance, book_layer, diff_orderbooks, http_client, liquidity_within,
+    mid_price, normalize_symbol, parse_seconds, send_checked, spread, spread_bps, symbol_url,
+    validate_orderbook, weight_limit, with_cumulative, ApiError, BinanceClient, Layer, Level,
+    OrderBook, OrderBookDelta, PriceBins, PriceData, Side, SymbolFilters, Ticker24h, Weight,
+    DEFAULT_CONNECT_TIMEOUT, DEFAULT_TIMEOUT,
+};
+use binance_price_checker::{binfixed, keyboard, ndjson, proxy, ws};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, NaiveTime, Utc};
+use clap::Parser;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures_util::{stream, StreamExt};
+use reqwest::{self, Client, StatusCode};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
 use std::{
+    backtrace::Backtrace,
     error::Error,
-    fs,
+    fs::{self, OpenOptions},
+    future::Future,
+    io::{self, Write},
     path::Path,
+    process::ExitCode,
     time::{Instant, SystemTime, UNIX_EPOCH},
 };
 use tokio::join;
+use tokio::sync::oneshot;
+use tracing::{debug_span, error, info, info_span, warn, Instrument};
+
+mod config;
+mod metrics;
+mod retention;
+mod serve;
+mod ws_serve;
 use tokio::time::{sleep, Duration};
 
-// Configuration constants - now using a float for more precise intervals
+// Configuration constants - now using a float for more precise intervals.
+// SYMBOL, API_BASE_URL, OUTPUT_DIR, DEPTH_LIMIT and UPDATE_INTERVAL are only defaults for Args.
 const SYMBOL: &str = "SUIUSDT";
+const API_BASE_URL: &str = "https://api.binance.us";
+const PARSE_ERROR_RETRIES: u32 = 2; // Extra fetch attempts under --on-parse-error retry
+const SECONDARY_API_BASE_URL: &str = "https://api.binance.com"; // Default for --secondary-base-url
 const OUTPUT_DIR: &str = "./orderbook_snapshots";
 const DEPTH_LIMIT: u32 = 100;
+const ALLOWED_DEPTH_LIMITS: &[u32] = &[5, 10, 20, 50, 100, 500, 1000, 5000]; // Accepted by /api/v3/depth
 const UPDATE_INTERVAL: f64 = 0.1; // Seconds (100ms)
 const MIN_INTERVAL_BETWEEN_SNAPSHOTS: f64 = 0.1; // Minimum time between snapshots (100ms)
+const LIQUIDITY_DISTANCES_BPS: &[f64] = &[5.0, 10.0, 25.0, 50.0, 100.0]; // Default for --liquidity-bps
+const PRICE_BIN_SPAN_BPS: f64 = 100.0; // Default for --price-bin-span-bps
+const OUTPUT_FORMAT: OutputFormat = OutputFormat::Json; // Default for --format
+const FORMAT_VERSION: u32 = 1; // Bump when the JSON or CSV snapshot layout changes
+const LOCAL_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S"; // local_datetime in saved records
+const QUARANTINE_DIR: &str = "quarantine"; // Subdirectory of --output-dir for --quarantine
+const FALLBACK_OUTPUT_DIR: &str = "./orderbook_snapshots_fallback"; // Used by --on-disk-full fallback
+const DISK_FULL_CHECK_INTERVAL: f64 = 5.0; // Seconds between free-space checks while paused
+const FOOTPRINT_PRICE_STEP: f64 = 0.001; // Footprint price bucket size
+const SCHEDULE_PING_INTERVAL: f64 = 60.0; // Seconds between /api/v3/ping health checks outside the schedule
+const MAX_CLOCK_SKEW_MS: f64 = 500.0; // Default for --max-clock-skew-ms
+const WEIGHT_BUDGET_FRACTION: f64 = 0.8; // Share of the per-minute request weight the capture may plan to use
+const DEFAULT_WEIGHT_LIMIT: u32 = 1200; // Assumed when exchangeInfo doesn't advertise a limit
+const TICKER_24H_WEIGHT: u32 = 2; // Single-symbol /api/v3/ticker/24hr, for --with-24h
+const WEIGHT_THROTTLE_FRACTION: f64 = 0.9; // Default for --weight-throttle-fraction
+const MAX_CONCURRENCY: NonZeroUsize = NonZeroUsize::new(8).unwrap(); // Default for --max-concurrency
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct OrderBook {
-    lastUpdateId: u64,
-    bids: Vec<[String; 2]>,
-    asks: Vec<[String; 2]>,
+// Log each span's busy/idle time when it closes. `fetch` and `save` spans are at info;
+// per-region fetch, parse, compute and write phases are at debug
+// (RUST_LOG=binance_price_checker=debug to see just ours)
+const SPAN_TIMINGS: bool = false;
+const ERROR_HISTORY_SIZE: usize = 50; // Recent errors kept for the panic dump
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Capture Binance order book and price snapshots",
+    after_help = "Exit codes: 0 success, 1 any other error, 2 invalid flags, config or \
+                  symbol, 3 rate-limited or banned by Binance, 4 network unreachable \
+                  (connection failed or timed out)"
+)]
+struct Args {
+    /// Read settings from a TOML file; flags given here override its values
+    #[arg(long, value_name = "FILE")]
+    config: Option<String>,
+
+    /// Print the effective settings, merged from --config and the command line, and exit
+    #[arg(long)]
+    print_config: bool,
+
+    /// Symbol to capture, or a comma-separated list (e.g. BTCUSDT,ETHUSDT)
+    #[arg(long, value_delimiter = ',', default_value = SYMBOL)]
+    symbol: Vec<String>,
+
+    /// REST host: `com`, `us`, or an https:// base URL
+    #[arg(long, default_value = API_BASE_URL)]
+    base_url: Endpoint,
+
+    /// Poll /api/v3/depth each interval, or keep a local book from the diff depth stream
+    #[arg(long, value_enum, default_value_t = Mode::Rest)]
+    mode: Mode,
+
+    /// WebSocket host for --mode ws; defaults to the stream host matching --base-url
+    #[arg(long, value_name = "WSS_URL")]
+    stream_url: Option<String>,
+
+    /// Seconds before a REST request is abandoned as timed out
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_TIMEOUT, value_parser = parse_seconds)]
+    timeout: f64,
+
+    /// Seconds allowed for establishing a connection
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_CONNECT_TIMEOUT, value_parser = parse_seconds)]
+    connect_timeout: f64,
+
+    /// Proxy for REST and WebSocket connections: http://, socks5:// or socks5h://,
+    /// optionally with user:pass@; defaults to HTTPS_PROXY or ALL_PROXY
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// Levels per side requested from /api/v3/depth
+    #[arg(long, default_value_t = DEPTH_LIMIT, value_parser = parse_depth_limit)]
+    depth_limit: u32,
+
+    /// Seconds between snapshots
+    #[arg(long, default_value_t = UPDATE_INTERVAL, value_parser = parse_interval)]
+    interval: f64,
+
+    /// Directory snapshots and derived files are written to
+    #[arg(long, default_value = OUTPUT_DIR)]
+    output_dir: String,
+
+    /// Snapshot encoding: nested JSON, one CSV row per level, or fixed-point binary
+    #[arg(long, value_enum, default_value_t = OUTPUT_FORMAT)]
+    format: OutputFormat,
+
+    /// One file per snapshot, or one appended `orderbook_<symbol>.ndjson` per symbol (JSON only)
+    #[arg(long, value_enum, default_value_t = OutputMode::Files)]
+    output_mode: OutputMode,
+
+    /// Save full books, or one full baseline per symbol followed by `delta_<symbol>_*`
+    /// records of the levels added, changed and removed since the last save (JSON only)
+    #[arg(long, value_enum, default_value_t = Output::Full)]
+    output: Output,
+
+    /// Rotate ndjson files before they grow past SIZE (e.g. 100MB; K/M/G are powers of 1024)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_file_size: Option<u64>,
+
+    /// Periodically delete the oldest orderbook_<symbol>_* files beyond a count (5000),
+    /// age (24h; s/m/h/d) or total size (10GB)
+    #[arg(long, value_name = "POLICY", value_parser = parse_retain)]
+    retain: Option<retention::Policy>,
+
+    /// Gzip each snapshot file (adding `.gz`), or the ndjson stream in --output-mode ndjson
+    #[arg(long)]
+    compress: bool,
+
+    /// Fetch once, check the book and price, print a pass/fail report and exit
+    #[arg(long)]
+    self_test: bool,
+
+    /// Capture one snapshot per symbol and exit with 0 if all were saved, or with the
+    /// code (below) of the worst failure across symbols
+    #[arg(long)]
+    once: bool,
+
+    /// Stop after this long (e.g. 90s, 30m, 2h 30m), the same way as on Ctrl+C
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    duration: Option<Duration>,
+
+    /// Ping the API N times, report round-trip latency and exit
+    #[arg(long, value_name = "N")]
+    probe_latency: Option<usize>,
+
+    /// Flag snapshots whose mid moved more than PCT percent from the last accepted mid
+    #[arg(long, value_name = "PCT", value_parser = parse_percent)]
+    max_tick_move: Option<f64>,
+
+    /// Discard --max-tick-move outliers instead of saving them flagged
+    #[arg(long, requires = "max_tick_move")]
+    drop_outliers: bool,
+
+    /// Don't save a snapshot whose lastUpdateId and ticker price both match the symbol's
+    /// last saved one; a price-only change is still saved
+    #[arg(long)]
+    skip_unchanged: bool,
+
+    /// Exit on Ctrl+C without capturing one last snapshot first
+    #[arg(long)]
+    no_final_snapshot: bool,
+
+    /// Add a `layers` object with top-5/20/100 spread and imbalance to each snapshot
+    #[arg(long)]
+    layered: bool,
+
+    /// Add a `cumulative` object with the running quantity and notional at each saved level
+    #[arg(long)]
+    cumulative: bool,
+
+    /// Log lines as readable text or as one JSON object per event (on stderr, filtered by RUST_LOG)
+    #[arg(long, value_enum, default_value_t = LogFormat::Human)]
+    log_format: LogFormat,
+
+    /// Print the "Snapshot saved" line for only every Nth snapshot per symbol; errors always print
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    log_sample: u64,
+
+    /// Fetch and save at most N symbols at once
+    #[arg(long, value_name = "N", default_value_t = MAX_CONCURRENCY)]
+    max_concurrency: NonZeroUsize,
+
+    /// Refuse to start, rather than warn, when the polling can't fit the weight budget
+    #[arg(long)]
+    strict_budget: bool,
+
+    /// Hold off until the next minute once reported request weight passes this share
+    /// of the limit
+    #[arg(long, value_name = "FRACTION", default_value_t = WEIGHT_THROTTLE_FRACTION, value_parser = parse_fraction)]
+    weight_throttle_fraction: f64,
+
+    /// Also fetch /api/v3/ticker/24hr each poll and store it as `ticker_24h` (weight 2 per symbol)
+    #[arg(long)]
+    with_24h: bool,
+
+    /// Save books that fail validation (unsorted, crossed, non-positive levels) under
+    /// `quarantine/` in the output directory instead of skipping them
+    #[arg(long)]
+    quarantine: bool,
+
+    /// Serve Prometheus metrics on http://ADDR/metrics (e.g. 127.0.0.1:9100)
+    #[arg(long, value_name = "ADDR")]
+    metrics_addr: Option<SocketAddr>,
+
+    /// Serve each symbol's latest snapshot on http://ADDR/orderbook and /price
+    #[arg(long, value_name = "ADDR")]
+    serve_addr: Option<SocketAddr>,
+
+    /// Push every new snapshot as a JSON frame to WebSocket clients on ws://ADDR/
+    #[arg(long, value_name = "ADDR")]
+    ws_serve_addr: Option<SocketAddr>,
+
+    /// When the output disk is full: wait for space, switch to
+    /// ./orderbook_snapshots_fallback, or stop; the snapshot that hit it is retried
+    #[arg(long, value_enum, default_value_t = DiskFullPolicy::Pause)]
+    on_disk_full: DiskFullPolicy,
+
+    /// When a book side is empty and mid is undefined: skip the snapshot, write its
+    /// mid-dependent fields as null/empty cells, or write them as 0
+    #[arg(long, value_enum, default_value_t = NanPolicy::Null)]
+    nan_policy: NanPolicy,
+
+    /// When a book or price response isn't valid JSON: drop that pass, refetch up to 2
+    /// more times, or drop it and keep the body under parse_errors/ in the output directory
+    #[arg(long, value_enum, default_value_t = ParseErrorPolicy::Skip)]
+    on_parse_error: ParseErrorPolicy,
+
+    /// Store `imbalance`, (bid - ask) / total over the fetched depth, summing base
+    /// quantity or quote notional; --layered and the summary use quantity without it
+    #[arg(long, value_enum, value_name = "WEIGHT")]
+    imbalance_weight: Option<Weight>,
+
+    /// Every SECS, write a `footprint_<symbol>_*.json` of the quantity seen at each
+    /// rounded price over the window
+    #[arg(long, value_name = "SECS", value_parser = parse_interval)]
+    footprint_window: Option<f64>,
+
+    /// Total each price's quantity over the footprint window, or average it per snapshot
+    #[arg(long, value_enum, default_value_t = FootprintAggregation::Sum, requires = "footprint_window")]
+    footprint_aggregation: FootprintAggregation,
+
+    /// Also write every snapshot file under PATH, in the same part_NNNNN layout;
+    /// failures there are logged, not fatal
+    #[arg(long, value_name = "PATH")]
+    mirror_dir: Option<String>,
+
+    /// Sort stored bids descending and asks ascending, so equal books give equal bytes
+    #[arg(long)]
+    canonicalize: bool,
+
+    /// Store prices and quantities cut to the decimals of the symbol's tick and step
+    /// size from exchangeInfo
+    #[arg(long)]
+    trim_to_precision: bool,
+
+    /// Store only the top N levels per side; everything computed still uses the full
+    /// --depth-limit fetch
+    #[arg(long, value_name = "N")]
+    save_levels: Option<NonZeroUsize>,
+
+    /// Append each snapshot's bid and ask quantity within --liquidity-bps of mid to
+    /// `liquidity_<symbol>.csv`
+    #[arg(long)]
+    liquidity_csv: bool,
+
+    /// Comma-separated distances from mid, in basis points, for --liquidity-csv columns
+    #[arg(long, value_name = "BPS", value_delimiter = ',', default_values_t = LIQUIDITY_DISTANCES_BPS.to_vec(), value_parser = parse_bps)]
+    liquidity_bps: Vec<f64>,
+
+    /// Store `price_bins`: the quantity in N equal-width bins on each side of mid
+    #[arg(long, value_name = "N")]
+    price_bins: Option<NonZeroUsize>,
+
+    /// Distance from mid, in basis points, the --price-bins cover on each side
+    #[arg(long, value_name = "BPS", default_value_t = PRICE_BIN_SPAN_BPS, value_parser = parse_bps, requires = "price_bins")]
+    price_bin_span_bps: f64,
+
+    /// Always fetch the full book instead of sending If-None-Match when the depth
+    /// endpoint supplies an ETag
+    #[arg(long)]
+    no_conditional_depth: bool,
+
+    /// Poll on the wall-clock grid of --interval shifted by this fraction of it, so
+    /// instances with different phases interleave
+    #[arg(long, value_name = "FRACTION", value_parser = parse_phase)]
+    phase: Option<f64>,
+
+    /// Capture only inside these time-of-day windows, pinging the API outside them:
+    /// "13:30-20:00 UTC" or "09:30-12:00,13:00-16:00 -05:00" (local time without a zone)
+    #[arg(long, value_name = "WINDOWS", value_parser = parse_schedule)]
+    schedule: Option<Schedule>,
+
+    /// Read single keys from the terminal: 'p' pauses and resumes, 's' snapshots now
+    #[arg(long)]
+    interactive: bool,
+
+    /// Race --base-url against --secondary-base-url each pass and keep the first
+    /// complete response; a region missing any of the symbols is dropped at startup
+    #[arg(long)]
+    redundant_regions: bool,
+
+    /// Second REST host for --redundant-regions: `com`, `us`, or an https:// base URL
+    #[arg(long, default_value = SECONDARY_API_BASE_URL, requires = "redundant_regions")]
+    secondary_base_url: Endpoint,
+
+    /// Compare the local clock with /api/v3/time every SECS, pausing capture while they
+    /// disagree by more than --max-clock-skew-ms
+    #[arg(long, value_name = "SECS", value_parser = parse_interval)]
+    clock_skew_check: Option<f64>,
+
+    /// Clock skew, in milliseconds either way, that pauses capture under --clock-skew-check
+    #[arg(long, value_name = "MS", default_value_t = MAX_CLOCK_SKEW_MS, value_parser = parse_millis, requires = "clock_skew_check")]
+    max_clock_skew_ms: f64,
+
+    /// Write snapshot files into numbered part_NNNNN subdirectories of --output-dir,
+    /// starting a new one after N files
+    #[arg(long, value_name = "N")]
+    max_snapshots_per_dir: Option<NonZeroUsize>,
+
+    /// If the process panics, write `panic_<timestamp>.txt` with the settings, recent
+    /// errors, last book and a backtrace to --output-dir
+    #[arg(long)]
+    panic_dump: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct PriceData {
-    price: String,
-    timestamp: u64,
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Rest,
+    Ws,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct CombinedData {
-    lastUpdateId: u64,
-    bids: Vec<[String; 2]>,
-    asks: Vec<[String; 2]>,
-    current_price: PriceData,
-    local_timestamp: u64,
-    local_datetime: String,
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum OutputMode {
+    Files,
+    Ndjson,
 }
 
-async fn get_current_price(client: &Client, symbol: &str) -> Result<PriceData, Box<dyn Error>> {
-    let url = format!(
-        "https://api.binance.us/api/v3/ticker/price?symbol={}",
-        symbol
-    );
+// What each save records: the whole book, or after a full baseline only its changes
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Output {
+    Full,
+    Delta,
+}
 
-    let response = client.get(&url).send().await?;
+fn parse_depth_limit(value: &str) -> Result<u32, String> {
+    let limit: u32 = value.parse().map_err(|e| format!("{}", e))?;
+    if ALLOWED_DEPTH_LIMITS.contains(&limit) {
+        Ok(limit)
+    } else {
+        Err(format!("must be one of {:?}", ALLOWED_DEPTH_LIMITS))
+    }
+}
 
-    if !response.status().is_success() {
-        return Err(format!("API Error getting price: {}", response.status()).into());
+fn parse_interval(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(seconds),
+        Ok(_) => Err("must be a positive number of seconds".to_string()),
+        Err(e) => Err(e.to_string()),
     }
+}
 
-    let price_data: serde_json::Value = response.json().await?;
-    let price = price_data["price"]
-        .as_str()
-        .ok_or("Failed to extract price")?
-        .to_string();
+fn parse_millis(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(ms) if ms > 0.0 && ms.is_finite() => Ok(ms),
+        Ok(_) => Err("must be a positive number of milliseconds".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
-    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+fn parse_percent(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(percent) if percent > 0.0 && percent.is_finite() => Ok(percent),
+        Ok(_) => Err("must be a positive percentage".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
-    Ok(PriceData { price, timestamp })
+fn parse_bps(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(bps) if bps > 0.0 && bps.is_finite() => Ok(bps),
+        Ok(_) => Err("must be a positive number of basis points".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
-async fn get_orderbook_snapshot(
-    client: &Client,
-    symbol: &str,
-    limit: u32,
-) -> Result<OrderBook, Box<dyn Error>> {
-    let url = format!(
-        "https://api.binance.us/api/v3/depth?symbol={}&limit={}",
-        symbol, limit
-    );
+fn parse_schedule(value: &str) -> Result<Schedule, String> {
+    Schedule::parse(value).map_err(|e| e.to_string())
+}
 
-    let response = client.get(&url).send().await?;
+fn parse_phase(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(phase) if (0.0..1.0).contains(&phase) => Ok(phase),
+        Ok(_) => Err("must be at least 0 and below 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
-    if !response.status().is_success() {
-        return Err(format!("API Error getting orderbook: {}", response.status()).into());
+fn parse_fraction(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(fraction),
+        Ok(_) => Err("must be above 0 and at most 1".to_string()),
+        Err(e) => Err(e.to_string()),
     }
+}
 
-    let orderbook: OrderBook = response.json().await?;
-    Ok(orderbook)
+// `--retain`: a file count (5000), an age with s/m/h/d (24h), or a size with B (10GB)
+fn parse_retain(value: &str) -> Result<retention::Policy, String> {
+    let value = value.trim();
+    if value.to_ascii_uppercase().ends_with('B') {
+        return parse_size(value).map(retention::Policy::Size);
+    }
+    let unit = match value.chars().last() {
+        Some('s') => Some(1),
+        Some('m') => Some(60),
+        Some('h') => Some(3600),
+        Some('d') => Some(86_400),
+        _ => None,
+    };
+    match unit {
+        Some(unit) => match value[..value.len() - 1].parse::<f64>() {
+            Ok(n) if n > 0.0 && n.is_finite() => Ok(retention::Policy::Age(
+                Duration::from_secs_f64(n * unit as f64),
+            )),
+            _ => Err(format!("'{}' is not an age like 24h", value)),
+        },
+        None => match value.parse::<usize>() {
+            Ok(count) if count > 0 => Ok(retention::Policy::Count(count)),
+            _ => Err(format!(
+                "'{}' is not a file count (5000), age (24h) or size (10GB)",
+                value
+            )),
+        },
+    }
 }
 
-async fn save_snapshot(
-    orderbook: &OrderBook,
-    price_data: &PriceData,
-    symbol: &str,
-) -> Result<String, Box<dyn Error>> {
-    // Create output directory if it doesn't exist
-    if !Path::new(OUTPUT_DIR).exists() {
-        fs::create_dir_all(OUTPUT_DIR)?;
+fn parse_size(value: &str) -> Result<u64, String> {
+    let upper = value.trim().to_ascii_uppercase();
+    let digits = upper.trim_end_matches('B');
+    let (number, multiplier) = match digits.char_indices().last() {
+        Some((i, 'K')) => (&digits[..i], 1 << 10),
+        Some((i, 'M')) => (&digits[..i], 1 << 20),
+        Some((i, 'G')) => (&digits[..i], 1 << 30),
+        _ => (digits, 1),
+    };
+    match number.trim().parse::<u64>() {
+        Ok(size) if size > 0 => size
+            .checked_mul(multiplier)
+            .ok_or_else(|| "size is too large".to_string()),
+        Ok(_) => Err("must be a positive size".to_string()),
+        Err(_) => Err(format!("'{}' is not a size like 100MB", value)),
     }
+}
 
-    // Format timestamp similar to Python version
-    let now = Local::now();
-    let timestamp_str = now.format("%Y%m%d_%H%M%S").to_string();
-    let datetime_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
+// On-disk encoding of saved snapshots; drives the file extension and `.meta` sidecar
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Json,
+    // One row per level; see CsvRow
+    Csv,
+    // Fixed-point integers scaled by tick/step size, see binfixed.rs
+    #[value(name = "binfixed")]
+    BinFixed,
+}
 
-    // Create filename
-    let filename = format!("{}/orderbook_{}_{}.json", OUTPUT_DIR, symbol, timestamp_str);
+impl OutputFormat {
+    fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::BinFixed => "binfixed",
+        }
+    }
 
-    // Get current timestamp
-    let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::BinFixed => "bin",
+        }
+    }
 
-    // Combine data
-    let combined_data = CombinedData {
-        lastUpdateId: orderbook.lastUpdateId,
-        bids: orderbook.bids.clone(),
-        asks: orderbook.asks.clone(),
-        current_price: PriceData {
-            price: price_data.price.clone(),
-            timestamp: price_data.timestamp,
-        },
-        local_timestamp: current_time,
-        local_datetime: datetime_str,
-    };
+    fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "application/json",
+            OutputFormat::Csv => "text/csv",
+            OutputFormat::BinFixed => "application/octet-stream",
+        }
+    }
 
-    // Serialize and save
-    let json_data = serde_json::to_string_pretty(&combined_data)?;
-    fs::write(&filename, json_data)?;
+    fn version(self) -> u32 {
+        match self {
+            OutputFormat::Json | OutputFormat::Csv => FORMAT_VERSION,
+            OutputFormat::BinFixed => binfixed::FORMAT_VERSION as u32,
+        }
+    }
+}
 
-    Ok(filename)
+// Per-symbol snapshot counter persisted in the output directory, so sequence
+// numbers keep increasing across restarts and gaps always mean lost snapshots
+struct SequenceCounter {
+    state_file: String,
+    next: u64,
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    println!("Starting orderbook snapshot capture for {}", SYMBOL);
-    println!(
-        "Saving snapshots approximately every {:.3}s to {}/",
-        UPDATE_INTERVAL, OUTPUT_DIR
-    );
-    println!(
-        "Minimum interval between snapshots: {:.3}s",
-        MIN_INTERVAL_BETWEEN_SNAPSHOTS
-    );
+impl SequenceCounter {
+    fn load(output_dir: &str, symbol: &str) -> Result<SequenceCounter, Box<dyn Error>> {
+        let state_file = format!("{}/sequence_{}.state", output_dir, symbol);
+        let next = match fs::read_to_string(&state_file) {
+            Ok(contents) => contents.trim().parse()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(SequenceCounter { state_file, next })
+    }
 
-    // Create a reusable HTTP client
-    let client = Arc::new(Client::new());
-    let mut last_snapshot_time = Instant::now();
+    fn advance(&mut self) -> u64 {
+        let sequence = self.next;
+        self.next += 1;
+        sequence
+    }
 
-    loop {
-        let iteration_start = Instant::now();
+    fn persist(&self) -> io::Result<()> {
+        if let Some(dir) = Path::new(&self.state_file).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.state_file, self.next.to_string())
+    }
+}
 
-        // Check if we should throttle to respect minimum interval
-        let time_since_last_snapshot = last_snapshot_time.elapsed().as_secs_f64();
-        if time_since_last_snapshot < MIN_INTERVAL_BETWEEN_SNAPSHOTS {
-            let sleep_duration =
-                Duration::from_secs_f64(MIN_INTERVAL_BETWEEN_SNAPSHOTS - time_since_last_snapshot);
-            sleep(sleep_duration).await;
+// Spreads snapshot files over numbered `part_NNNNN` subdirectories so no single
+// directory accumulates millions of entries
+struct DirectoryRotation {
+    max_files: Option<usize>, // None = write straight into the output directory
+    part: u32,
+    files_in_part: usize,
+}
+
+impl DirectoryRotation {
+    // Continue after the highest existing part rather than topping up an old one
+    fn resume(output_dir: &str, max_files: Option<usize>) -> DirectoryRotation {
+        let last_part = fs::read_dir(output_dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()?
+                    .strip_prefix("part_")?
+                    .parse::<u32>()
+                    .ok()
+            })
+            .max();
+        DirectoryRotation {
+            max_files,
+            part: last_part.map_or(0, |part| part + 1),
+            files_in_part: 0,
+        }
+    }
+
+    // Directory the next snapshot under `output_dir` should be written to
+    fn next_dir(&mut self, output_dir: &str) -> String {
+        let Some(max_files) = self.max_files else {
+            return output_dir.to_string();
+        };
+        if self.files_in_part >= max_files {
+            self.part += 1;
+            self.files_in_part = 0;
         }
+        format!("{}/part_{:05}", output_dir, self.part)
+    }
 
-        // Update last snapshot time
-        last_snapshot_time = Instant::now();
+    fn record_saved(&mut self) {
+        self.files_in_part += 1;
+    }
+}
+
+// How mid-dependent computed fields (price bins, liquidity, summary spread and
+// imbalance) are written when the book has an empty side and mid is undefined
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum NanPolicy {
+    // Don't save the snapshot or its derived rows at all
+    Skip,
+    // JSON null / empty CSV cells
+    Null,
+    Zero,
+}
+
+// What to do when a book or price response isn't the JSON we expect
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum ParseErrorPolicy {
+    // Drop this iteration's snapshot and carry on
+    Skip,
+    // Refetch up to PARSE_ERROR_RETRIES more times
+    Retry,
+    // Skip, but keep the body in the output directory's parse_errors/ for inspection
+    SaveRaw,
+}
+
+// What to do when a save fails because the output disk is out of space (ENOSPC)
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum DiskFullPolicy {
+    // Wait until a probe write succeeds, then retry the save
+    Pause,
+    // Retry the save in FALLBACK_OUTPUT_DIR and keep writing there
+    Fallback,
+    Exit,
+}
+
+// How quantity observed at a price bucket is combined across a footprint window
+#[derive(clap::ValueEnum, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum FootprintAggregation {
+    Sum,
+    Mean,
+}
+
+// Quantity seen at each rounded price level over a window of snapshots
+struct Footprint {
+    window_start: Instant,
+    window_start_ms: u64,
+    snapshots: usize,
+    bids: BTreeMap<i64, f64>,
+    asks: BTreeMap<i64, f64>,
+}
+
+#[derive(Serialize, Debug)]
+struct FootprintRecord {
+    symbol: String,
+    window_start_ms: u64,
+    window_end_ms: u64,
+    snapshots: usize,
+    price_step: f64,
+    aggregation: FootprintAggregation,
+    bids: Vec<[f64; 2]>,
+    asks: Vec<[f64; 2]>,
+}
+
+impl Footprint {
+    fn new() -> Result<Footprint, Box<dyn Error>> {
+        Ok(Footprint {
+            window_start: Instant::now(),
+            window_start_ms: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+            snapshots: 0,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+        })
+    }
+
+    fn record(&mut self, orderbook: &OrderBook) {
+        for (levels, buckets) in [
+            (&orderbook.bids, &mut self.bids),
+            (&orderbook.asks, &mut self.asks),
+        ] {
+            for level in levels {
+                let bucket = (level.price_f64() / FOOTPRINT_PRICE_STEP).round() as i64;
+                *buckets.entry(bucket).or_insert(0.0) += level.qty_f64();
+            }
+        }
+        self.snapshots += 1;
+    }
+
+    fn is_complete(&self, window: f64) -> bool {
+        self.window_start.elapsed().as_secs_f64() >= window
+    }
+
+    async fn save(
+        &self,
+        symbol: &str,
+        output_dir: &str,
+        aggregation: FootprintAggregation,
+    ) -> Result<String, Box<dyn Error>> {
+        // Mean treats a level missing from a snapshot as zero quantity in that snapshot
+        let divisor = match aggregation {
+            FootprintAggregation::Sum => 1.0,
+            FootprintAggregation::Mean => self.snapshots.max(1) as f64,
+        };
+        let to_levels = |buckets: &BTreeMap<i64, f64>| -> Vec<[f64; 2]> {
+            buckets
+                .iter()
+                .map(|(&bucket, &qty)| [bucket as f64 * FOOTPRINT_PRICE_STEP, qty / divisor])
+                .collect()
+        };
 
-        // Execute both API calls in parallel
-        let client_ref = &client;
-        let (orderbook_result, price_result) = join!(
-            get_orderbook_snapshot(client_ref, SYMBOL, DEPTH_LIMIT),
-            get_current_price(client_ref, SYMBOL)
+        let mut bids = to_levels(&self.bids);
+        bids.reverse(); // Best (highest) bid first, matching the order book layout
+        let record = FootprintRecord {
+            symbol: symbol.to_string(),
+            window_start_ms: self.window_start_ms,
+            window_end_ms: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+            snapshots: self.snapshots,
+            price_step: FOOTPRINT_PRICE_STEP,
+            aggregation,
+            bids,
+            asks: to_levels(&self.asks),
+        };
+
+        fs::create_dir_all(output_dir)?;
+        let filename = format!(
+            "{}/footprint_{}_{}.json",
+            output_dir,
+            symbol,
+            Local::now().format("%Y%m%d_%H%M%S")
         );
+        fs::write(&filename, serde_json::to_string_pretty(&record)?)?;
+        Ok(filename)
+    }
+}
 
-        match (orderbook_result, price_result) {
-            (Ok(snapshot), Ok(price_data)) => {
-                match save_snapshot(&snapshot, &price_data, SYMBOL).await {
-                    Ok(filename) => {
-                        let total_time = iteration_start.elapsed().as_secs_f64();
-                        println!("Snapshot saved to {} in {:.3}s", filename, total_time);
-                    }
-                    Err(e) => eprintln!("Error saving snapshot: {}", e),
-                }
+// Time-of-day windows during which capture runs. A window whose end is before
+// its start wraps past midnight.
+#[derive(Debug, Clone)]
+struct Schedule {
+    spec: String, // As given, for logging
+    windows: Vec<(NaiveTime, NaiveTime)>,
+    // None = the machine's local time zone
+    offset: Option<FixedOffset>,
+}
+
+impl Schedule {
+    // "HH:MM-HH:MM[,HH:MM-HH:MM...] [UTC|local|+HH:MM|-HH:MM]"
+    fn parse(spec: &str) -> Result<Schedule, Box<dyn Error>> {
+        let (windows_spec, zone) = match spec.trim().split_once(' ') {
+            Some((windows, zone)) => (windows, zone.trim()),
+            None => (spec.trim(), "local"),
+        };
+
+        let offset = match zone {
+            "local" => None,
+            "UTC" | "utc" | "Z" => Some(FixedOffset::east_opt(0).ok_or("invalid offset")?),
+            _ => Some(
+                zone.parse::<FixedOffset>()
+                    .map_err(|e| format!("Invalid schedule time zone '{}': {}", zone, e))?,
+            ),
+        };
+
+        let mut windows = Vec::new();
+        for window in windows_spec.split(',') {
+            let (start, end) = window.split_once('-').ok_or_else(|| {
+                format!("Invalid schedule window '{}': expected HH:MM-HH:MM", window)
+            })?;
+            let parse_time = |t: &str| {
+                NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                    .map_err(|e| format!("Invalid schedule time '{}': {}", t, e))
+            };
+            windows.push((parse_time(start)?, parse_time(end)?));
+        }
+        Ok(Schedule {
+            spec: spec.trim().to_string(),
+            windows,
+            offset,
+        })
+    }
+
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        let time = match self.offset {
+            Some(offset) => now.with_timezone(&offset).time(),
+            None => now.with_timezone(&Local).time(),
+        };
+        self.windows.iter().any(|&(start, end)| {
+            if start <= end {
+                start <= time && time < end
+            } else {
+                time >= start || time < end
             }
-            (Err(e), _) => eprintln!("Failed to get orderbook snapshot: {}", e),
-            (_, Err(e)) => eprintln!("Failed to get price data: {}", e),
+        })
+    }
+}
+
+// Running totals for the one-row summary.csv entry written at shutdown
+struct SessionStats {
+    started: DateTime<Local>,
+    snapshots: u64,
+    spreads: Vec<f64>,
+    imbalance_sum: f64,
+    imbalance_count: u64,
+    last_saved: Option<Instant>,
+    max_gap: f64, // Seconds between consecutive saved snapshots
+}
+
+impl SessionStats {
+    fn new() -> SessionStats {
+        SessionStats {
+            started: Local::now(),
+            snapshots: 0,
+            spreads: Vec::new(),
+            imbalance_sum: 0.0,
+            imbalance_count: 0,
+            last_saved: None,
+            max_gap: 0.0,
         }
+    }
 
-        // Calculate if we need to sleep to maintain the desired interval
-        let elapsed = iteration_start.elapsed().as_secs_f64();
-        if elapsed < UPDATE_INTERVAL {
-            let sleep_duration = Duration::from_secs_f64(UPDATE_INTERVAL - elapsed);
-            sleep(sleep_duration).await;
+    fn record_saved(&mut self, orderbook: &OrderBook, capture: &CaptureInfo) {
+        self.snapshots += 1;
+        if let Some(last) = self.last_saved {
+            self.max_gap = self.max_gap.max(last.elapsed().as_secs_f64());
+        }
+        self.last_saved = Some(Instant::now());
+
+        // Undefined values are left out of the averages unless --nan-policy counts them as zero
+        let zero = (capture.nan_policy == NanPolicy::Zero).then_some(0.0);
+        let best = |levels: &[Level]| levels.first().map(Level::price_f64);
+        let spread = match (best(&orderbook.bids), best(&orderbook.asks)) {
+            (Some(bid), Some(ask)) => Some(ask - bid),
+            _ => zero,
+        };
+        if let Some(spread) = spread {
+            self.spreads.push(spread);
+        }
+        let weight = capture.imbalance_weight.unwrap_or(Weight::Base);
+        if let Some(imbalance) = book_imbalance(orderbook, weight).or(zero) {
+            self.imbalance_sum += imbalance;
+            self.imbalance_count += 1;
+        }
+    }
+
+    fn append_summary(
+        &self,
+        symbol: &str,
+        output_dir: &str,
+        error_count: u64,
+        mirror_failures: u64,
+    ) -> Result<String, Box<dyn Error>> {
+        fs::create_dir_all(output_dir)?;
+        let filename = format!("{}/summary.csv", output_dir);
+        let is_new = !Path::new(&filename).exists();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&filename)?;
+
+        if is_new {
+            writeln!(
+                file,
+                "symbol,start,end,snapshots,mean_spread,median_spread,mean_imbalance,max_gap_secs,errors,mirror_failures"
+            )?;
+        }
+
+        // Empty fields rather than zeros when nothing was captured
+        let mut spreads = self.spreads.clone();
+        spreads.sort_by(f64::total_cmp);
+        let (mean_spread, median_spread) = if spreads.is_empty() {
+            (String::new(), String::new())
         } else {
-            println!("Processing took longer than interval ({:.3}s)", elapsed);
+            let mean = spreads.iter().sum::<f64>() / spreads.len() as f64;
+            let mid = spreads.len() / 2;
+            let median = if spreads.len().is_multiple_of(2) {
+                (spreads[mid - 1] + spreads[mid]) / 2.0
+            } else {
+                spreads[mid]
+            };
+            (mean.to_string(), median.to_string())
+        };
+        let mean_imbalance = if self.imbalance_count == 0 {
+            String::new()
+        } else {
+            (self.imbalance_sum / self.imbalance_count as f64).to_string()
+        };
+
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{:.3},{},{}",
+            symbol,
+            self.started.format("%Y-%m-%d %H:%M:%S"),
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            self.snapshots,
+            mean_spread,
+            median_spread,
+            mean_imbalance,
+            self.max_gap,
+            error_count,
+            mirror_failures
+        )?;
+        Ok(filename)
+    }
+}
+
+// State shared by the symbol passes of one iteration
+struct CaptureContext<'a> {
+    args: &'a Args,
+    apis: &'a [BinanceClient],
+    symbol_filters: &'a HashMap<String, SymbolFilters>,
+    rotation: &'a RefCell<DirectoryRotation>,
+    output_dir: &'a RefCell<String>,
+    latest: Option<&'a serve::Latest>, // Set with --serve-addr
+    subscribers: Option<&'a ws_serve::Subscribers>, // Set with --ws-serve-addr
+    controls: &'a keyboard::Controls,
+}
+
+impl CaptureContext<'_> {
+    fn publishing(&self) -> bool {
+        self.latest.is_some() || self.subscribers.is_some()
+    }
+
+    // Hand a snapshot to --serve-addr and --ws-serve-addr
+    fn publish(&self, symbol: &str, data: &CombinedData) {
+        if let Some(latest) = self.latest {
+            serve::publish(latest, symbol, data.clone());
+        }
+        if let Some(subscribers) = self.subscribers {
+            ws_serve::publish(subscribers, data);
+        }
+    }
+}
+
+// Per-symbol capture state; each --symbol gets its own sequence, footprint and summary row
+struct SymbolCapture {
+    symbol: String,
+    depth_limit: u32, // --depth-limit unless the config file overrides it for this symbol
+    interval: f64,    // Likewise for --interval
+    next_due: Option<Instant>,
+    sequence: SequenceCounter,
+    footprint: Footprint,
+    stats: SessionStats,
+    last_mid: Option<f64>, // Reference for --max-tick-move; outliers never replace it
+    stream: Option<Arc<ws::DepthBook>>, // Set in --mode ws
+    ndjson: Option<ndjson::NdjsonWriter>, // Set in --output-mode ndjson
+    last_saved: Option<(u64, String)>, // lastUpdateId and price, for --skip-unchanged
+    resync_pending: bool,  // The stream resynced since the last saved snapshot
+    delta_base: Option<OrderBook>, // Last saved book (as stored) for --output delta to diff against
+}
+
+impl SymbolCapture {
+    // Whether this symbol's own interval has come round by `now`, scheduling the next
+    // poll if so. `slack` absorbs the main loop waking slightly early.
+    fn take_due(&mut self, now: Instant, slack: Duration) -> bool {
+        if self.next_due.is_some_and(|due| now + slack < due) {
+            return false;
         }
+        let interval = Duration::from_secs_f64(self.interval);
+        let next = self.next_due.map_or(now, |due| due + interval);
+        // Behind after a pause or a slow pass: restart the schedule from now
+        self.next_due = Some(if next <= now { now + interval } else { next });
+        true
+    }
+}
+
+// One `--output delta` record; apply it to the book saved with base_update_id
+#[derive(Serialize, Debug)]
+struct DeltaData {
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: u64,
+    base_update_id: u64,
+    #[serde(flatten)]
+    delta: OrderBookDelta,
+    current_price: PriceData,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticker_24h: Option<Ticker24h>,
+    local_timestamp: u64,
+    local_datetime: String,
+    sequence: u64,
+    source: String,
+}
+
+#[derive(Serialize, Debug)]
+struct SnapshotMeta {
+    format: &'static str,
+    content_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_encoding: Option<&'static str>, // "gzip" with --compress
+    version: u32,
+}
+
+// Wall-clock time spent on each request of the parallel fetch
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+struct FetchLatency {
+    orderbook: f64,
+    price: f64,
+}
+
+// How and when a snapshot was captured, recorded alongside the book
+struct CaptureInfo {
+    fetch_latency: FetchLatency,
+    source: String,
+    sequence: u64,
+    depth_limit: u32,
+    outlier: Option<bool>, // None unless --max-tick-move is set
+    layered: bool,
+    format: OutputFormat,
+    compress: bool,
+    nan_policy: NanPolicy,
+    imbalance_weight: Option<Weight>,
+    canonicalize: bool,
+    save_levels: Option<usize>,
+    price_bins: Option<usize>,
+    price_bin_span_bps: f64,
+    cumulative: bool,
+    trim_to_precision: bool,
+    resync: bool,
+}
+
+// Book and price fetched together from one API region
+struct RegionFetch {
+    source: String,
+    orderbook: Result<OrderBook, Box<dyn Error>>,
+    price: Result<PriceData, Box<dyn Error>>,
+    ticker_24h: Option<Result<Ticker24h, Box<dyn Error>>>, // Only fetched with --with-24h
+    latency: FetchLatency,
+    // The depth endpoint answered 304 and `orderbook` is the cached copy, or the
+    // streamed book has had no updates since the previous read
+    book_unchanged: bool,
+    resync: bool, // First read of the streamed book after it reconnected
+}
+
+impl RegionFetch {
+    fn is_ok(&self) -> bool {
+        self.orderbook.is_ok() && self.price.is_ok()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CombinedData {
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: u64,
+    bids: Vec<[String; 2]>,
+    asks: Vec<[String; 2]>,
+    current_price: PriceData,
+    local_timestamp: u64,
+    local_datetime: String,
+    depth_limit: u32,
+    saved_levels: usize,
+    fetch_latency_ms: FetchLatency,
+    source: String,
+    sequence: u64,
+    // Top of book from the full fetch; each is omitted when a side it needs is empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    best_bid: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    best_ask: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spread: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spread_bps: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mid_price: Option<Decimal>,
+    // Only with --with-24h, and omitted when that request failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ticker_24h: Option<Ticker24h>,
+    // None = --price-bins off (field omitted), Some(None) = undefined for this book (null)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    price_bins: Option<Option<PriceBins>>,
+    // Same None/Some(None) convention as price_bins, per --imbalance-weight
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    imbalance: Option<Option<f64>>,
+    // Only written with --cumulative
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cumulative: Option<CumulativeDepth>,
+    // Only written when --max-tick-move is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tick_move_outlier: Option<bool>,
+    // Only written with --layered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    layers: Option<Layers>,
+    // Only written, as true, on the first snapshot after the depth stream resynced;
+    // lastUpdateId jumps there rather than following on from the previous snapshot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resync: Option<bool>,
+}
+
+// [cum_qty, cum_notional] for each stored level, index for index with bids and asks
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CumulativeDepth {
+    bids: Vec<[Decimal; 2]>,
+    asks: Vec<[Decimal; 2]>,
+}
+
+// Features of the book truncated to a few depths, all from the one fetch
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Layers {
+    l5: Layer,
+    l20: Layer,
+    l100: Layer,
+}
+
+// Recent state kept for the panic hook's diagnostic dump and the session summary
+struct Diagnostics {
+    last_snapshot: Option<OrderBook>,
+    recent_errors: VecDeque<String>,
+    error_count: u64,
+    mirror_failures: u64,
+}
+
+static DIAGNOSTICS: Mutex<Diagnostics> = Mutex::new(Diagnostics {
+    last_snapshot: None,
+    recent_errors: VecDeque::new(),
+    error_count: 0,
+    mirror_failures: 0,
+});
+
+fn report_error(message: String) {
+    error!("{}", message);
+    if let Ok(mut diagnostics) = DIAGNOSTICS.lock() {
+        diagnostics.error_count += 1;
+        if diagnostics.recent_errors.len() == ERROR_HISTORY_SIZE {
+            diagnostics.recent_errors.pop_front();
+        }
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        diagnostics
+            .recent_errors
+            .push_back(format!("[{}] {}", timestamp, message));
+    }
+}
+
+fn config_summary(args: &Args) -> String {
+    format!(
+        "symbol={} base_url={} output_dir={} format={:?} output_mode={:?} depth_limit={} interval={} MIN_INTERVAL_BETWEEN_SNAPSHOTS={} \
+         save_levels={:?} liquidity_csv={} liquidity_bps={:?}",
+        args.symbol.join(","),
+        args.base_url,
+        args.output_dir,
+        args.format,
+        args.output_mode,
+        args.depth_limit,
+        args.interval,
+        MIN_INTERVAL_BETWEEN_SNAPSHOTS,
+        args.save_levels,
+        args.liquidity_csv,
+        args.liquidity_bps
+    )
+}
+
+fn install_panic_hook(args: &Args) {
+    let config = config_summary(args);
+    let output_dir = args.output_dir.clone();
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let mut dump = format!("Panic: {}\n\nConfig: {}\n", info, config);
+
+        // try_lock: the panicking thread may be the one holding the lock
+        match DIAGNOSTICS.try_lock() {
+            Ok(diagnostics) => {
+                dump.push_str("\nRecent errors:\n");
+                for error in &diagnostics.recent_errors {
+                    dump.push_str(&format!("  {}\n", error));
+                }
+                let snapshot = diagnostics
+                    .last_snapshot
+                    .as_ref()
+                    .and_then(|book| serde_json::to_string_pretty(book).ok())
+                    .unwrap_or_else(|| "none".to_string());
+                dump.push_str(&format!("\nLast snapshot:\n{}\n", snapshot));
+            }
+            Err(_) => dump.push_str("\nDiagnostics unavailable (state locked)\n"),
+        }
+
+        dump.push_str(&format!("\nBacktrace:\n{}\n", Backtrace::force_capture()));
+
+        let filename = format!(
+            "{}/panic_{}.txt",
+            output_dir,
+            Local::now().format("%Y%m%d_%H%M%S")
+        );
+        match fs::create_dir_all(&output_dir).and_then(|_| fs::write(&filename, dump)) {
+            Ok(()) => eprintln!("Diagnostic dump written to {}", filename),
+            Err(e) => eprintln!("Failed to write diagnostic dump: {}", e),
+        }
+
+        default_hook(info);
+    }));
+}
+
+// Not every symbol trades in every region; exchangeInfo answers 400 for unlisted symbols
+async fn is_symbol_listed(
+    client: &Client,
+    base_url: &str,
+    symbol: &str,
+) -> Result<bool, Box<dyn Error>> {
+    let url = symbol_url(base_url, "/api/v3/exchangeInfo", symbol, "")?;
+    match send_checked(client, &url, "getting exchange info").await {
+        Ok(_) => Ok(true),
+        Err(e) => match e.downcast_ref::<ApiError>() {
+            Some(ApiError::Http {
+                status: StatusCode::BAD_REQUEST,
+                ..
+            }) => Ok(false),
+            Some(_) => Err(e),
+            // Can't tell while unreachable; keep the region and let regular fetches decide
+            None => Ok(true),
+        },
+    }
+}
+
+// Cut a decimal string to `decimals` places. Binance values are multiples of the
+// tick/step, so only trailing zeros are dropped and no rounding is needed.
+fn trim_decimals(value: &str, decimals: usize) -> String {
+    match value.split_once('.') {
+        Some((whole, _)) if decimals == 0 => whole.to_string(),
+        Some((whole, fraction)) if fraction.len() > decimals => {
+            format!("{}.{}", whole, &fraction[..decimals])
+        }
+        _ => value.to_string(),
+    }
+}
+
+// One full exchangeInfo fetch, indexed for just the tracked symbols
+async fn get_symbol_filters(
+    api: &BinanceClient,
+    symbols: &[String],
+) -> Result<HashMap<String, SymbolFilters>, Box<dyn Error>> {
+    let info = api.get_exchange_info(None).await?;
+    let mut filters_by_symbol = HashMap::with_capacity(symbols.len());
+    for symbol in symbols {
+        filters_by_symbol.insert(symbol.clone(), SymbolFilters::parse(&info, symbol)?);
+    }
+    Ok(filters_by_symbol)
+}
+
+// Tick and step size as binfixed 1e-8 units
+fn filter_units(filters: &SymbolFilters) -> Result<(u64, u64), Box<dyn Error>> {
+    Ok((
+        binfixed::parse_units(&filters.tick_size.to_string())?,
+        binfixed::parse_units(&filters.step_size.to_string())?,
+    ))
+}
+
+// Per-minute REQUEST_WEIGHT limit from `symbol`'s exchangeInfo
+async fn get_weight_limit(api: &BinanceClient, symbol: &str) -> Result<u32, Box<dyn Error>> {
+    let info = api.get_exchange_info(Some(symbol)).await?;
+    Ok(weight_limit(&info)?)
+}
+
+// Request weight of one /api/v3/depth call, per Binance's published tiers
+fn depth_weight(limit: u32) -> u32 {
+    match limit {
+        0..=100 => 5,
+        101..=500 => 25,
+        501..=1000 => 50,
+        _ => 250,
+    }
+}
+
+// Check the planned polling fits in the weight budget; Err carries a feasible interval.
+// `polls` holds each symbol's weight per poll and its interval
+fn check_request_budget(polls: &[(u32, f64)], weight_limit: u32) -> Result<(), String> {
+    // Plus one single-symbol ticker/price call (weight 2) per symbol
+    let needed_per_minute: f64 = polls
+        .iter()
+        .map(|&(weight, interval)| {
+            (weight + 2) as f64 * 60.0 / interval.max(MIN_INTERVAL_BETWEEN_SNAPSHOTS)
+        })
+        .sum();
+    let budget = weight_limit as f64 * WEIGHT_BUDGET_FRACTION;
+
+    if needed_per_minute <= budget {
+        return Ok(());
+    }
+    Err(format!(
+        "{} symbol(s) need ~{:.0} request weight/min but the budget is {:.0} \
+         ({:.0}% of {}); make the intervals at least {:.2}x longer",
+        polls.len(),
+        needed_per_minute,
+        budget,
+        WEIGHT_BUDGET_FRACTION * 100.0,
+        weight_limit,
+        needed_per_minute / budget
+    ))
+}
+
+// Under --on-parse-error save-raw, keep an unparseable body in {output_dir}/parse_errors/
+fn save_unparseable_body(
+    error: &(dyn Error + 'static),
+    output_dir: &str,
+    on_parse_error: ParseErrorPolicy,
+) {
+    if on_parse_error != ParseErrorPolicy::SaveRaw {
+        return;
+    }
+    if let Some(ApiError::Parse { context, body, .. }) = error.downcast_ref::<ApiError>() {
+        match save_raw_body(output_dir, body, context) {
+            Ok(filename) => info!("Saved unparseable response to {}", filename),
+            Err(e) => report_error(format!("Error saving unparseable response: {}", e)),
+        }
+    }
+}
+
+fn save_raw_body(output_dir: &str, body: &str, context: &str) -> io::Result<String> {
+    let dir = format!("{}/parse_errors", output_dir);
+    fs::create_dir_all(&dir)?;
+    let filename = format!(
+        "{}/{}_{}.txt",
+        dir,
+        context.replace(' ', "_"),
+        Local::now().format("%Y%m%d_%H%M%S%.3f")
+    );
+    fs::write(&filename, body)?;
+    Ok(filename)
+}
+
+// Run `fetch`, repeating it on parse errors under --on-parse-error retry
+async fn retry_on_parse_error<T, F, Fut>(
+    on_parse_error: ParseErrorPolicy,
+    mut fetch: F,
+) -> Result<T, Box<dyn Error>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn Error>>>,
+{
+    let extra_attempts = match on_parse_error {
+        ParseErrorPolicy::Retry => PARSE_ERROR_RETRIES,
+        _ => 0,
+    };
+    let mut attempt = 0;
+    loop {
+        let result = fetch().await;
+        let is_parse_error = result
+            .as_ref()
+            .err()
+            .and_then(|e| e.downcast_ref::<ApiError>())
+            .is_some_and(|e| matches!(e, ApiError::Parse { .. }));
+        if !is_parse_error || attempt >= extra_attempts {
+            return result;
+        }
+        attempt += 1;
+        report_error(format!(
+            "Parse error, retrying ({}/{})",
+            attempt, extra_attempts
+        ));
+    }
+}
+
+// Write to `{path}.tmp` and rename into place, so a reader tailing the directory
+// never sees a partial file. Readers only match `.json`/`.bin`, so leftovers are ignored.
+fn write_atomic(path: &str, data: &[u8]) -> io::Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    if let Err(e) = fs::write(&tmp_path, data) {
+        // Don't leave a partial file taking up space on a full disk
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    fs::rename(&tmp_path, path)
+}
+
+fn mirror_snapshot(mirror_dir: &str, name: &str, data: &[u8], meta: &str) -> io::Result<()> {
+    fs::create_dir_all(mirror_dir)?;
+    let filename = format!("{}/{}", mirror_dir, name);
+    write_atomic(&filename, data)?;
+    write_atomic(&format!("{}.meta", filename), meta.as_bytes())
+}
+
+// Where save_snapshot puts a snapshot, per --output-mode
+enum Destination<'a> {
+    Files {
+        output_dir: &'a str,
+        mirror_dir: Option<&'a str>,
+    },
+    Ndjson(&'a mut ndjson::NdjsonWriter),
+}
+
+// The full record for one snapshot, as saved and as served on --serve-addr
+fn combined_data(
+    orderbook: &OrderBook,
+    price_data: &PriceData,
+    ticker_24h: Option<&Ticker24h>,
+    capture: &CaptureInfo,
+    filters: Option<&SymbolFilters>,
+) -> Result<CombinedData, Box<dyn Error>> {
+    // Format timestamp similar to Python version
+    let datetime_str = Local::now().format(LOCAL_DATETIME_FORMAT).to_string();
+
+    // Get current timestamp
+    let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    let _compute = debug_span!("compute").entered();
+
+    // Trim stored depth; anything computed from the book should use the full fetch
+    let format_level = |level: &Level| -> [String; 2] {
+        let [price, qty] = level.to_strings();
+        match filters {
+            Some(f) if capture.trim_to_precision => [
+                trim_decimals(&price, f.price_decimals()),
+                trim_decimals(&qty, f.qty_decimals()),
+            ],
+            _ => [price, qty],
+        }
+    };
+    let stored = stored_book(orderbook, capture);
+    let bids: Vec<[String; 2]> = stored.bids.iter().map(format_level).collect();
+    let asks: Vec<[String; 2]> = stored.asks.iter().map(format_level).collect();
+    let saved_levels = bids.len().max(asks.len());
+    let price_bins = match capture.price_bins {
+        Some(count) => match bin_by_distance(orderbook, count, capture.price_bin_span_bps) {
+            Some(bins) => Some(Some(bins)),
+            None if capture.nan_policy == NanPolicy::Zero => Some(Some(PriceBins {
+                span_bps: capture.price_bin_span_bps,
+                bids: vec![0.0; count],
+                asks: vec![0.0; count],
+            })),
+            None => Some(None),
+        },
+        None => None,
+    };
+    let cumulative = capture.cumulative.then(|| {
+        let totals = |side| {
+            with_cumulative(&stored, side)
+                .iter()
+                .map(|level| [level.cum_qty, level.cum_notional])
+                .collect()
+        };
+        CumulativeDepth {
+            bids: totals(Side::Bid),
+            asks: totals(Side::Ask),
+        }
+    });
+    let imbalance =
+        capture
+            .imbalance_weight
+            .map(|weight| match book_imbalance(orderbook, weight) {
+                Some(imbalance) => Some(imbalance),
+                None if capture.nan_policy == NanPolicy::Zero => Some(0.0),
+                None => None,
+            });
+
+    let best_bid = orderbook.bids.first().map(|level| level.price);
+    let best_ask = orderbook.asks.first().map(|level| level.price);
+    let spread = spread(orderbook);
+    let mid_price = mid_price(orderbook);
+    let spread_bps = spread_bps(orderbook);
+
+    Ok(CombinedData {
+        last_update_id: orderbook.last_update_id,
+        bids,
+        asks,
+        current_price: PriceData {
+            price: match filters {
+                Some(f) if capture.trim_to_precision => {
+                    trim_decimals(&price_data.price, f.price_decimals())
+                }
+                _ => price_data.price.clone(),
+            },
+            timestamp: price_data.timestamp,
+        },
+        local_timestamp: current_time,
+        local_datetime: datetime_str,
+        depth_limit: capture.depth_limit,
+        saved_levels,
+        fetch_latency_ms: capture.fetch_latency,
+        source: capture.source.clone(),
+        sequence: capture.sequence,
+        best_bid,
+        best_ask,
+        spread,
+        spread_bps,
+        mid_price,
+        ticker_24h: ticker_24h.cloned(),
+        price_bins,
+        imbalance,
+        cumulative,
+        tick_move_outlier: capture.outlier,
+        layers: if capture.layered {
+            Some(Layers {
+                l5: layer(orderbook, 5, capture),
+                l20: layer(orderbook, 20, capture),
+                l100: layer(orderbook, 100, capture),
+            })
+        } else {
+            None
+        },
+        resync: capture.resync.then_some(true),
+    })
+}
+
+async fn save_snapshot(
+    combined_data: CombinedData,
+    capture: &CaptureInfo,
+    symbol: &str,
+    destination: Destination<'_>,
+    filters: Option<&SymbolFilters>,
+) -> Result<String, Box<dyn Error>> {
+    // Named after the capture time recorded in the file plus the sequence number,
+    // since passes under a second apart share the same local_datetime
+    let timestamp_str =
+        NaiveDateTime::parse_from_str(&combined_data.local_datetime, LOCAL_DATETIME_FORMAT)?
+            .format("%Y%m%d_%H%M%S")
+            .to_string();
+
+    let (output_dir, mirror_dir) = match destination {
+        Destination::Files {
+            output_dir,
+            mirror_dir,
+        } => (output_dir, mirror_dir),
+        Destination::Ndjson(writer) => {
+            let _write = debug_span!("append").entered();
+            let line = serde_json::to_vec(&combined_data)?;
+            return Ok(writer.append(&line)?.to_string());
+        }
+    };
+
+    // Serialize and save
+    let data = match capture.format {
+        OutputFormat::Json => serde_json::to_string_pretty(&combined_data)?.into_bytes(),
+        OutputFormat::Csv => {
+            encode_csv(&combined_data, combined_data.mid_price, capture.nan_policy)?
+        }
+        OutputFormat::BinFixed => {
+            let filters = filters.ok_or("binfixed output requires the symbol's tick/step size")?;
+            let (tick_units, step_units) = filter_units(filters)?;
+            binfixed::encode(&binfixed::FixedBook {
+                symbol: symbol.to_string(),
+                tick_units,
+                step_units,
+                last_update_id: combined_data.last_update_id,
+                local_timestamp: combined_data.local_timestamp,
+                price: combined_data.current_price.price,
+                price_timestamp: combined_data.current_price.timestamp,
+                bids: combined_data.bids,
+                asks: combined_data.asks,
+            })?
+        }
+    };
+    write_snapshot_file(
+        output_dir,
+        mirror_dir,
+        &format!(
+            "orderbook_{}_{}_{}",
+            symbol, timestamp_str, capture.sequence
+        ),
+        data,
+        capture,
+    )
+}
+
+// Write `<stem>.<ext>[.gz]` and its `.meta` sidecar into `output_dir`, and copy both
+// to `mirror_dir` if set; returns the primary file name
+fn write_snapshot_file(
+    output_dir: &str,
+    mirror_dir: Option<&str>,
+    stem: &str,
+    data: Vec<u8>,
+    capture: &CaptureInfo,
+) -> Result<String, Box<dyn Error>> {
+    let mut name = format!("{}.{}", stem, capture.format.extension());
+    if capture.compress {
+        name.push_str(".gz");
+    }
+    let filename = format!("{}/{}", output_dir, name);
+
+    let _write = debug_span!("write", file = %filename).entered();
+    // Create output directory if it doesn't exist
+    if !Path::new(output_dir).exists() {
+        fs::create_dir_all(output_dir)?;
+    }
+
+    let data = if capture.compress {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data)?;
+        encoder.finish()?
+    } else {
+        data
+    };
+    write_atomic(&filename, &data)?;
+
+    // Record the exact encoding next to the data so readers never have to guess
+    let meta = SnapshotMeta {
+        format: capture.format.name(),
+        content_type: capture.format.content_type(),
+        content_encoding: capture.compress.then_some("gzip"),
+        version: capture.format.version(),
+    };
+    let meta = serde_json::to_string(&meta)?;
+    write_atomic(&format!("{}.meta", filename), meta.as_bytes())?;
+
+    if let Some(mirror_dir) = mirror_dir {
+        if let Err(e) = mirror_snapshot(mirror_dir, &name, &data, &meta) {
+            report_error(format!("Error mirroring snapshot to {}: {}", mirror_dir, e));
+            if let Ok(mut diagnostics) = DIAGNOSTICS.lock() {
+                diagnostics.mirror_failures += 1;
+            }
+        }
+    }
+
+    Ok(filename)
+}
+
+// The levels a snapshot stores: sorted with --canonicalize, at most --save-levels per side
+fn stored_book(orderbook: &OrderBook, capture: &CaptureInfo) -> OrderBook {
+    let mut stored = orderbook.clone();
+    if capture.canonicalize {
+        // Before trimming, so --save-levels keeps the true top of book
+        stored
+            .bids
+            .sort_by_key(|level| std::cmp::Reverse(level.price));
+        stored.asks.sort_by_key(|level| level.price);
+    }
+    if let Some(save_levels) = capture.save_levels {
+        stored.bids.truncate(save_levels);
+        stored.asks.truncate(save_levels);
+    }
+    stored
+}
+
+// `--output delta`: the stored levels that changed since `base`, the symbol's
+// previous saved book
+fn save_delta(
+    base: &OrderBook,
+    orderbook: &OrderBook,
+    price_data: &PriceData,
+    ticker_24h: Option<&Ticker24h>,
+    capture: &CaptureInfo,
+    symbol: &str,
+    destination: Destination<'_>,
+) -> Result<String, Box<dyn Error>> {
+    let now = Local::now();
+    let record = DeltaData {
+        last_update_id: orderbook.last_update_id,
+        base_update_id: base.last_update_id,
+        delta: diff_orderbooks(base, &stored_book(orderbook, capture)),
+        current_price: price_data.clone(),
+        ticker_24h: ticker_24h.cloned(),
+        local_timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        local_datetime: now.format(LOCAL_DATETIME_FORMAT).to_string(),
+        sequence: capture.sequence,
+        source: capture.source.clone(),
+    };
+    match destination {
+        Destination::Ndjson(writer) => {
+            let _write = debug_span!("append").entered();
+            let line = serde_json::to_vec(&record)?;
+            Ok(writer.append(&line)?.to_string())
+        }
+        Destination::Files {
+            output_dir,
+            mirror_dir,
+        } => write_snapshot_file(
+            output_dir,
+            mirror_dir,
+            // Down to the millisecond: passes can be 0.1s apart
+            &format!("delta_{}_{}", symbol, now.format("%Y%m%d_%H%M%S_%3f")),
+            serde_json::to_string_pretty(&record)?.into_bytes(),
+            capture,
+        ),
+    }
+}
+
+// One level of a CSV snapshot. The snapshot-wide fields repeat on every row so each
+// file loads as a single flat table; price_bins, imbalance, cumulative and layers are JSON-only.
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    side: &'static str,
+    price: &'a str,
+    qty: &'a str,
+    last_update_id: u64,
+    local_timestamp: u64,
+    mid_price: &'a str,
+    local_datetime: &'a str,
+    current_price: &'a str,
+    sequence: u64,
+    source: &'a str,
+}
+
+// Levels are written as the strings stored in `data`, never reparsed
+fn encode_csv(
+    data: &CombinedData,
+    mid: Option<Decimal>,
+    nan_policy: NanPolicy,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mid_price = match mid {
+        Some(mid) => mid.normalize().to_string(),
+        None if nan_policy == NanPolicy::Zero => "0".to_string(),
+        None => String::new(),
+    };
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for (side, levels) in [("bid", &data.bids), ("ask", &data.asks)] {
+        for [price, qty] in levels {
+            writer.serialize(CsvRow {
+                side,
+                price,
+                qty,
+                last_update_id: data.last_update_id,
+                local_timestamp: data.local_timestamp,
+                mid_price: &mid_price,
+                local_datetime: &data.local_datetime,
+                current_price: &data.current_price.price,
+                sequence: data.sequence,
+                source: &data.source,
+            })?;
+        }
+    }
+    Ok(writer.into_inner().map_err(|e| e.to_string())?)
+}
+
+// Mid of the best bid and ask; None when either side is empty
+fn book_mid(orderbook: &OrderBook) -> Option<f64> {
+    mid_price(orderbook)?.to_f64()
+}
+
+// Spread and imbalance over the best `depth` levels per side, undefined values
+// written per --nan-policy
+fn layer(orderbook: &OrderBook, depth: usize, capture: &CaptureInfo) -> Layer {
+    let weight = capture.imbalance_weight.unwrap_or(Weight::Base);
+    let mut layer = book_layer(orderbook, depth, weight);
+    if capture.nan_policy == NanPolicy::Zero {
+        layer.vwap_spread.get_or_insert(0.0);
+        layer.imbalance.get_or_insert(0.0);
+    }
+    layer
+}
+
+async fn append_liquidity_row(
+    orderbook: &OrderBook,
+    symbol: &str,
+    output_dir: &str,
+    distances_bps: &[f64],
+    nan_policy: NanPolicy,
+) -> Result<(), Box<dyn Error>> {
+    let mid = book_mid(orderbook);
+    if mid.is_none() && nan_policy == NanPolicy::Skip {
+        return Ok(());
+    }
+
+    let filename = format!("{}/liquidity_{}.csv", output_dir, symbol);
+    let is_new = !Path::new(&filename).exists();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&filename)?;
+
+    if is_new {
+        let mut header = String::from("local_timestamp,lastUpdateId,mid_price");
+        for distance in distances_bps {
+            header.push_str(&format!(",bid_{}bps,ask_{}bps", distance, distance));
+        }
+        writeln!(file, "{}", header)?;
+    }
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let mut row = format!("{},{}", timestamp, orderbook.last_update_id);
+    match mid {
+        Some(mid) => {
+            row.push_str(&format!(",{}", mid));
+            for &distance in distances_bps {
+                let liquidity = |side| liquidity_within(orderbook, side, distance).unwrap_or(0.0);
+                row.push_str(&format!(
+                    ",{},{}",
+                    liquidity(Side::Bid),
+                    liquidity(Side::Ask)
+                ));
+            }
+        }
+        None => {
+            let cell = if nan_policy == NanPolicy::Zero {
+                "0"
+            } else {
+                ""
+            };
+            for _ in 0..1 + 2 * distances_bps.len() {
+                row.push(',');
+                row.push_str(cell);
+            }
+        }
+    }
+    writeln!(file, "{}", row)?;
+
+    Ok(())
+}
+
+// Local clock minus server clock in ms, using the request midpoint to cancel out latency
+async fn measure_clock_skew(client: &Client, base_url: &str) -> Result<f64, Box<dyn Error>> {
+    let sent = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64() * 1000.0;
+    let url = format!("{}/api/v3/time", base_url);
+    let response = send_checked(client, &url, "getting server time").await?;
+    let body: serde_json::Value = response.json().await?;
+    let received = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64() * 1000.0;
+
+    let server_time = body["serverTime"]
+        .as_u64()
+        .ok_or("Failed to extract serverTime")? as f64;
+    Ok((sent + received) / 2.0 - server_time)
+}
+
+// Fetch book and price in parallel from one region, timing each request individually
+async fn fetch_from_region(
+    api: &BinanceClient,
+    symbol: &str,
+    depth_limit: u32,
+    with_24h: bool,
+    on_parse_error: ParseErrorPolicy,
+) -> RegionFetch {
+    let base_url = api.base_url();
+    let ((orderbook, orderbook_latency), (price, price_latency), ticker_24h) = join!(
+        async {
+            let start = Instant::now();
+            let fetch = || api.get_orderbook_snapshot(symbol, depth_limit);
+            let result = retry_on_parse_error(on_parse_error, fetch).await;
+            (result, start.elapsed().as_secs_f64() * 1000.0)
+        }
+        .instrument(debug_span!("fetch_depth", region = base_url)),
+        async {
+            let start = Instant::now();
+            let result =
+                retry_on_parse_error(on_parse_error, || api.get_current_price(symbol)).await;
+            (result, start.elapsed().as_secs_f64() * 1000.0)
+        }
+        .instrument(debug_span!("fetch_price", region = base_url)),
+        fetch_24h(api, symbol, with_24h, on_parse_error)
+    );
+
+    let (orderbook, book_unchanged) = match orderbook {
+        Ok((orderbook, unchanged)) => (Ok(orderbook), unchanged),
+        Err(e) => (Err(e), false),
+    };
+
+    RegionFetch {
+        source: base_url.trim_start_matches("https://").to_string(),
+        orderbook,
+        book_unchanged,
+        resync: false,
+        price,
+        ticker_24h,
+        latency: FetchLatency {
+            orderbook: orderbook_latency,
+            price: price_latency,
+        },
+    }
+}
+
+fn rest_client(client: &Client, base_url: &str, args: &Args) -> BinanceClient {
+    BinanceClient::new(client.clone(), base_url)
+        .conditional_depth(!args.no_conditional_depth)
+        .on_retry(report_error)
+}
+
+// Book from the local depth stream copy, price from REST
+async fn fetch_from_stream(
+    api: &BinanceClient,
+    stream: &ws::DepthBook,
+    symbol: &str,
+    depth_limit: u32,
+    with_24h: bool,
+    on_parse_error: ParseErrorPolicy,
+) -> RegionFetch {
+    let start = Instant::now();
+    let price = retry_on_parse_error(on_parse_error, || api.get_current_price(symbol))
+        .instrument(debug_span!("fetch_price", region = api.base_url()));
+    let ticker_24h = fetch_24h(api, symbol, with_24h, on_parse_error);
+    let (price, ticker_24h) = join!(price, ticker_24h);
+    let price_latency = start.elapsed().as_secs_f64() * 1000.0;
+
+    // Read after the price request so the book is as fresh as possible
+    let (orderbook, book_unchanged, resync) = match stream.read(depth_limit) {
+        Some(read) => (Ok(read.orderbook), read.unchanged, read.resync),
+        None => (Err("Depth stream is not in sync yet".into()), false, false),
+    };
+
+    RegionFetch {
+        source: stream.source().to_string(),
+        orderbook,
+        book_unchanged,
+        resync,
+        price,
+        ticker_24h,
+        latency: FetchLatency {
+            orderbook: 0.0,
+            price: price_latency,
+        },
+    }
+}
+
+// /api/v3/ticker/24hr alongside the price, when --with-24h asks for it
+async fn fetch_24h(
+    api: &BinanceClient,
+    symbol: &str,
+    with_24h: bool,
+    on_parse_error: ParseErrorPolicy,
+) -> Option<Result<Ticker24h, Box<dyn Error>>> {
+    if !with_24h {
+        return None;
+    }
+    let result = retry_on_parse_error(on_parse_error, || api.get_24h_ticker(symbol))
+        .instrument(debug_span!("fetch_24h", region = api.base_url()))
+        .await;
+    Some(result)
+}
+
+// Query every region at once and keep the first complete response. If all fail,
+// the first region's errors are reported.
+async fn fetch_redundant(
+    apis: &[BinanceClient],
+    symbol: &str,
+    depth_limit: u32,
+    with_24h: bool,
+    on_parse_error: ParseErrorPolicy,
+) -> RegionFetch {
+    if let [primary_api, secondary_api, ..] = apis {
+        let fetch = |api| fetch_from_region(api, symbol, depth_limit, with_24h, on_parse_error);
+        let primary = fetch(primary_api);
+        let secondary = fetch(secondary_api);
+        tokio::pin!(primary, secondary);
+
+        return tokio::select! {
+            first = &mut primary => {
+                if first.is_ok() {
+                    first
+                } else {
+                    let second = secondary.await;
+                    if second.is_ok() {
+                        second
+                    } else {
+                        first
+                    }
+                }
+            }
+            first = &mut secondary => {
+                if first.is_ok() {
+                    first
+                } else {
+                    // Either primary succeeds or its errors are the ones worth reporting
+                    primary.await
+                }
+            }
+        };
+    }
+
+    fetch_from_region(&apis[0], symbol, depth_limit, with_24h, on_parse_error).await
+}
+
+// One live fetch checked end to end; returns whether every check passed
+async fn self_test(api: &BinanceClient, symbol: &str, depth_limit: u32) -> bool {
+    // A parse error should fail the check, not be retried past
+    let fetch = fetch_from_region(api, symbol, depth_limit, false, ParseErrorPolicy::Skip).await;
+    let mut checks: Vec<(&str, Result<(), String>)> = Vec::new();
+
+    let orderbook = fetch.orderbook.map_err(|e| e.to_string());
+    let price = fetch.price.map_err(|e| e.to_string());
+    checks.push((
+        "fetch order book",
+        orderbook.as_ref().map(|_| ()).map_err(Clone::clone),
+    ));
+    checks.push((
+        "fetch price",
+        price.as_ref().map(|_| ()).map_err(Clone::clone),
+    ));
+
+    if let Ok(orderbook) = &orderbook {
+        let invariants = if orderbook.bids.is_empty() || orderbook.asks.is_empty() {
+            Err("book has an empty side".to_string())
+        } else {
+            validate_orderbook(orderbook).map_err(|e| e.to_string())
+        };
+        let book_ok = invariants.is_ok();
+        checks.push(("book invariants", invariants));
+
+        if let (true, Ok(price)) = (book_ok, &price) {
+            let in_range = match price.price.parse::<Decimal>() {
+                Ok(value) => {
+                    let best_bid = orderbook.bids[0].price;
+                    let best_ask = orderbook.asks[0].price;
+                    if (best_bid..=best_ask).contains(&value) {
+                        Ok(())
+                    } else {
+                        Err(format!(
+                            "price {} outside bid-ask {} - {}",
+                            value, best_bid, best_ask
+                        ))
+                    }
+                }
+                Err(e) => Err(format!("unparseable price '{}': {}", price.price, e)),
+            };
+            checks.push(("price within bid-ask", in_range));
+        }
+    }
+
+    println!("Self-test against {} for {}:", fetch.source, symbol);
+    let mut passed = true;
+    for (name, result) in &checks {
+        match result {
+            Ok(()) => println!("  PASS {}", name),
+            Err(reason) => {
+                passed = false;
+                println!("  FAIL {}: {}", name, reason);
+            }
+        }
+    }
+    println!(
+        "Self-test {} (book {:.1}ms, price {:.1}ms)",
+        if passed { "passed" } else { "FAILED" },
+        fetch.latency.orderbook,
+        fetch.latency.price
+    );
+    passed
+}
+
+// Time until the next point on the wall-clock grid `k * interval + phase * interval`.
+// The grid is anchored to the Unix epoch, so separate instances line up with each other.
+fn next_grid_delay(interval: f64, phase: f64) -> Duration {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let offset = phase * interval;
+    let next = ((now - offset) / interval).floor() * interval + offset + interval;
+    Duration::from_secs_f64((next - now).max(0.0))
+}
+
+// Round-trip /api/v3/ping `count` times and report the latency distribution
+async fn probe_latency(
+    client: &Client,
+    base_url: &str,
+    count: usize,
+) -> Result<(), Box<dyn Error>> {
+    let url = format!("{}/api/v3/ping", base_url);
+    println!("Probing {} with {} pings", url, count);
+
+    let mut samples = Vec::with_capacity(count);
+    let mut failures = 0;
+    for _ in 0..count {
+        let start = Instant::now();
+        match send_checked(client, &url, "pinging").await {
+            Ok(_) => samples.push(start.elapsed().as_secs_f64() * 1000.0),
+            Err(e) => {
+                failures += 1;
+                report_error(format!("Ping failed: {}", e));
+            }
+        }
+    }
+    if samples.is_empty() {
+        return Err(format!("All {} pings to {} failed", count, base_url).into());
+    }
+
+    // Jitter as the mean change between consecutive round trips (RFC 3550 style)
+    let jitter = if samples.len() > 1 {
+        samples.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / (samples.len() - 1) as f64
+    } else {
+        0.0
+    };
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let mut sorted = samples.clone();
+    sorted.sort_by(f64::total_cmp);
+    let p95 = sorted[((sorted.len() as f64 * 0.95).ceil() as usize).saturating_sub(1)];
+
+    println!(
+        "{} ok, {} failed; min {:.1}ms mean {:.1}ms p95 {:.1}ms max {:.1}ms jitter {:.1}ms",
+        samples.len(),
+        failures,
+        sorted[0],
+        mean,
+        p95,
+        sorted[sorted.len() - 1],
+        jitter
+    );
+    Ok(())
+}
+
+fn is_disk_full(error: &(dyn Error + 'static)) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::StorageFull)
+}
+
+// Block capture until a probe write to `output_dir` succeeds again; false if Ctrl+C
+// came first
+async fn wait_for_free_space(output_dir: &str, controls: &keyboard::Controls) -> bool {
+    warn!(
+        "Capture paused: output disk is full, checking again every {:.1}s",
+        DISK_FULL_CHECK_INTERVAL
+    );
+    let probe = format!("{}/.space_probe", output_dir);
+    while !controls.is_shutting_down() {
+        controls
+            .wait(Duration::from_secs_f64(DISK_FULL_CHECK_INTERVAL))
+            .await;
+        if controls.is_shutting_down() {
+            break;
+        }
+        if fs::write(&probe, [0u8; 64 * 1024]).is_ok() {
+            let _ = fs::remove_file(&probe);
+            info!("Disk space available again, resuming capture");
+            return true;
+        }
+    }
+    false
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            Failure::of(e.as_ref()).exit_code()
+        }
+    }
+}
+
+async fn run() -> Result<ExitCode, Box<dyn Error>> {
+    let (args, effective) = config::load().map_err(|e| InvalidConfig(e.to_string()))?;
+    if args.print_config {
+        print!("{}", effective.render());
+        return Ok(ExitCode::SUCCESS);
+    }
+    logging::init(args.log_format, SPAN_TIMINGS);
+    if let Some(path) = &args.config {
+        info!("Loaded settings from {}", path);
+    }
+
+    // Validate once up front so a bad symbol fails fast instead of erroring every iteration
+    let mut symbols: Vec<String> = Vec::new();
+    for symbol in &args.symbol {
+        let symbol = normalize_symbol(symbol).map_err(InvalidConfig)?;
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    let mut overrides = HashMap::new();
+    for (symbol, setting) in effective.overrides() {
+        let symbol = normalize_symbol(symbol).map_err(InvalidConfig)?;
+        if !symbols.contains(&symbol) {
+            warn!(
+                "Ignoring config overrides for {}, which isn't captured",
+                symbol
+            );
+        }
+        overrides.insert(symbol, setting);
+    }
+    // Depth limit and interval for one symbol
+    let settings_for = |symbol: &str| {
+        let setting = overrides.get(symbol);
+        (
+            setting
+                .and_then(|s| s.depth_limit)
+                .unwrap_or(args.depth_limit),
+            setting.and_then(|s| s.interval).unwrap_or(args.interval),
+        )
+    };
+    // The loop runs at the shortest interval; slower symbols sit out passes until due
+    let interval = symbols
+        .iter()
+        .map(|symbol| settings_for(symbol).1)
+        .fold(f64::INFINITY, f64::min);
+
+    // Create a reusable HTTP client
+    let proxy = proxy::resolve(args.proxy.as_deref()).map_err(InvalidConfig)?;
+    let client = Arc::new(http_client(
+        Duration::from_secs_f64(args.timeout),
+        Duration::from_secs_f64(args.connect_timeout),
+        proxy.as_ref(),
+    )?);
+
+    // One-shot diagnostic modes
+    if args.self_test {
+        let mut passed = true;
+        for symbol in &symbols {
+            let api = rest_client(&client, args.base_url.url(), &args);
+            passed &= self_test(&api, symbol, settings_for(symbol).0).await;
+        }
+        return Ok(if passed {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        });
+    }
+    if let Some(count) = args.probe_latency {
+        probe_latency(&client, args.base_url.url(), count).await?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    info!(
+        "Starting orderbook snapshot capture for {}",
+        symbols.join(", ")
+    );
+    info!(
+        "Saving snapshots approximately every {:.3}s to {}/",
+        interval, args.output_dir
+    );
+    info!(
+        "Minimum interval between snapshots: {:.3}s",
+        MIN_INTERVAL_BETWEEN_SNAPSHOTS
+    );
+    for symbol in symbols.iter().filter(|s| overrides.contains_key(*s)) {
+        let (depth_limit, symbol_interval) = settings_for(symbol);
+        info!(
+            "{}: depth {} every {:.3}s",
+            symbol, depth_limit, symbol_interval
+        );
+    }
+    if let Some(levels) = args.save_levels {
+        info!(
+            "Fetching depth {} but saving only the top {} levels per side",
+            args.depth_limit, levels
+        );
+    }
+
+    if let Some(phase) = args.phase {
+        info!(
+            "Polling on a {:.3}s grid offset by {:.3}s (phase {})",
+            interval,
+            phase * interval,
+            phase
+        );
+    }
+
+    if args.panic_dump {
+        install_panic_hook(&args);
+    }
+
+    let mut last_snapshot_time = Instant::now();
+    let output_dir = args.output_dir.clone();
+
+    let mut regions = vec![args.base_url.url()];
+    if args.redundant_regions && args.secondary_base_url.url() != args.base_url.url() {
+        regions.push(args.secondary_base_url.url());
+        // A region is only usable if it lists every symbol being captured
+        let mut listed = Vec::new();
+        'regions: for region in regions {
+            for symbol in &symbols {
+                match is_symbol_listed(&client, region, symbol).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        warn!(
+                            "{} is not listed on {}, skipping that region",
+                            symbol, region
+                        );
+                        continue 'regions;
+                    }
+                    Err(e) => {
+                        report_error(format!("Skipping region {}: {}", region, e));
+                        continue 'regions;
+                    }
+                }
+            }
+            listed.push(region);
+        }
+        if listed.is_empty() {
+            return Err(InvalidConfig(format!(
+                "{} is not listed in any configured region",
+                symbols.join(", ")
+            ))
+            .into());
+        }
+        regions = listed;
+    }
+
+    // One client per region; each keeps its own conditional depth cache
+    let apis: Vec<BinanceClient> = regions
+        .iter()
+        .map(|region| rest_client(&client, region, &args))
+        .collect();
+
+    // The stream only needs a REST depth snapshot when it (re)syncs, not every poll
+    let (rest_depth, stream_base) = match args.mode {
+        Mode::Rest => (true, None),
+        Mode::Ws => {
+            let base = match &args.stream_url {
+                Some(url) if url.starts_with("wss://") => url.trim_end_matches('/').to_string(),
+                Some(url) => {
+                    let message = format!("--stream-url '{}' is not a wss:// URL", url);
+                    return Err(InvalidConfig(message).into());
+                }
+                None => endpoint::stream_base(&args.base_url)
+                    .ok_or_else(|| {
+                        InvalidConfig(
+                            "--mode ws with a custom --base-url needs --stream-url".to_string(),
+                        )
+                    })?
+                    .to_string(),
+            };
+            info!("Maintaining order books from the depth stream at {}", base);
+            (false, Some(base))
+        }
+    };
+
+    let polls: Vec<(u32, f64)> = symbols
+        .iter()
+        .map(|symbol| {
+            let (depth_limit, symbol_interval) = settings_for(symbol);
+            let depth = if rest_depth {
+                depth_weight(depth_limit)
+            } else {
+                0
+            };
+            let ticker_24h = if args.with_24h { TICKER_24H_WEIGHT } else { 0 };
+            (depth + ticker_24h, symbol_interval)
+        })
+        .collect();
+    // Every region gets the full load, so checking the first one is enough
+    let weight_limit = match get_weight_limit(&apis[0], &symbols[0]).await {
+        Ok(limit) => limit,
+        Err(e) => {
+            report_error(format!(
+                "Using default weight limit {}: {}",
+                DEFAULT_WEIGHT_LIMIT, e
+            ));
+            DEFAULT_WEIGHT_LIMIT
+        }
+    };
+    if let Err(warning) = check_request_budget(&polls, weight_limit) {
+        if args.strict_budget {
+            return Err(InvalidConfig(warning).into());
+        }
+        report_error(format!("WARNING: capture will fall behind: {}", warning));
+    }
+
+    let symbol_filters = if args.format == OutputFormat::BinFixed || args.trim_to_precision {
+        let filters = get_symbol_filters(&apis[0], &symbols).await?;
+        for (name, f) in &filters {
+            // Fail now rather than on the first binfixed save
+            let (tick_units, step_units) = filter_units(f)?;
+            info!(
+                "{}: {} price / {} quantity decimals, tick {} / step {} (1e-8 units)",
+                name,
+                f.price_decimals(),
+                f.qty_decimals(),
+                tick_units,
+                step_units
+            );
+        }
+        filters
+    } else {
+        HashMap::new()
+    };
+
+    if args.output == Output::Delta && args.format != OutputFormat::Json {
+        let message = format!("--output delta needs --format json, not {:?}", args.format);
+        return Err(InvalidConfig(message).into());
+    }
+    if args.output_mode == OutputMode::Ndjson && args.format != OutputFormat::Json {
+        let message = format!(
+            "--output-mode ndjson needs --format json, not {:?}",
+            args.format
+        );
+        return Err(InvalidConfig(message).into());
+    }
+
+    let mut captures = Vec::with_capacity(symbols.len());
+    for symbol in &symbols {
+        let (depth_limit, symbol_interval) = settings_for(symbol);
+        captures.push(SymbolCapture {
+            symbol: symbol.clone(),
+            depth_limit,
+            interval: symbol_interval,
+            next_due: None,
+            sequence: SequenceCounter::load(&output_dir, symbol)?,
+            footprint: Footprint::new()?,
+            stats: SessionStats::new(),
+            last_mid: None,
+            resync_pending: false,
+            stream: stream_base.as_deref().map(|base| {
+                ws::spawn(
+                    rest_client(&client, regions[0], &args),
+                    base,
+                    symbol,
+                    proxy.clone(),
+                    report_error,
+                )
+            }),
+            ndjson: match args.output_mode {
+                OutputMode::Files => None,
+                OutputMode::Ndjson => Some(ndjson::NdjsonWriter::open(
+                    &output_dir,
+                    &format!("orderbook_{}", symbol),
+                    args.max_file_size,
+                    args.compress,
+                )?),
+            },
+            last_saved: None,
+            delta_base: None,
+        });
+    }
+    if let Some(policy) = args.retain {
+        retention::spawn(output_dir.clone(), symbols.clone(), policy);
+    }
+    let rotation = RefCell::new(DirectoryRotation::resume(
+        &output_dir,
+        args.max_snapshots_per_dir.map(NonZeroUsize::get),
+    ));
+    // Shared by the concurrent symbol passes; a disk-full fallback switches it for all
+    let output_dir = RefCell::new(output_dir);
+
+    let mut last_skew_check: Option<Instant> = None;
+    let mut skew_paused = false;
+
+    let schedule = &args.schedule;
+    if let Some(schedule) = schedule {
+        info!("Capturing only during {}", schedule.spec);
+    }
+    let mut schedule_idle = false;
+    let mut last_pass: Option<PassOutcome> = None; // Worst outcome of the latest pass, for --once
+
+    let controls = Arc::new(keyboard::Controls::default());
+    keyboard::watch_ctrl_c(controls.clone());
+    let _terminal_guard = if args.interactive {
+        Some(keyboard::spawn(controls.clone())?)
+    } else {
+        None
+    };
+
+    let metrics_server = match args.metrics_addr {
+        Some(addr) => {
+            let (shutdown, receiver) = oneshot::channel();
+            Some((shutdown, metrics::serve(addr, receiver).await?))
+        }
+        None => None,
+    };
+    let latest = args.serve_addr.map(|_| serve::Latest::default());
+    let snapshot_server = match (args.serve_addr, &latest) {
+        (Some(addr), Some(latest)) => {
+            let (shutdown, receiver) = oneshot::channel();
+            let server = serve::serve(addr, latest.clone(), symbols.clone(), receiver).await?;
+            Some((shutdown, server))
+        }
+        _ => None,
+    };
+    let subscribers = args.ws_serve_addr.map(|_| ws_serve::channel());
+    let push_server = match (args.ws_serve_addr, &subscribers) {
+        (Some(addr), Some(subscribers)) => {
+            let (shutdown, receiver) = oneshot::channel();
+            let server = ws_serve::serve(addr, subscribers.clone(), receiver).await?;
+            Some((shutdown, server))
+        }
+        _ => None,
+    };
+
+    // Aligned start: the first fetch lands on this instance's grid point
+    if let Some(phase) = args.phase {
+        sleep(next_grid_delay(interval, phase)).await;
+    }
+
+    // Whichever comes first, this or Ctrl+C, ends the capture; a pass that is
+    // running finishes either way
+    if let Some(duration) = args.duration {
+        let controls = controls.clone();
+        tokio::spawn(async move {
+            sleep(duration).await;
+            if !controls.is_shutting_down() {
+                info!(
+                    "--duration {} is up, shutting down",
+                    humantime::format_duration(duration)
+                );
+                controls.shut_down();
+            }
+        });
+    }
+    let capture_start = Instant::now();
+
+    loop {
+        // After Ctrl+C, one last forced pass so the capture ends on the current book
+        let final_pass = controls.is_shutting_down();
+        if final_pass && args.no_final_snapshot {
+            break;
+        }
+        if final_pass {
+            info!("Capturing a final snapshot before exiting");
+        }
+
+        // A forced snapshot goes through even while paused
+        let forced = controls.take_force_snapshot() || final_pass || args.once;
+        if controls.is_paused() && !forced {
+            controls.wait(Duration::from_millis(250)).await;
+            continue;
+        }
+
+        if let Some(schedule) = schedule {
+            if !forced && !schedule.is_active(Utc::now()) {
+                if !schedule_idle {
+                    info!("Outside the capture schedule, idling");
+                    schedule_idle = true;
+                }
+                let ping_url = format!("{}/api/v3/ping", regions[0]);
+                if let Err(e) = send_checked(&client, &ping_url, "pinging").await {
+                    report_error(format!("Health ping failed while idle: {}", e));
+                }
+                controls
+                    .wait(Duration::from_secs_f64(SCHEDULE_PING_INTERVAL))
+                    .await;
+                continue;
+            }
+            if schedule_idle {
+                info!("Capture window open, resuming");
+                schedule_idle = false;
+            }
+        }
+
+        if let Some(check_interval) = args.clock_skew_check {
+            let max_skew = args.max_clock_skew_ms;
+            let due = last_skew_check.is_none_or(|t| t.elapsed().as_secs_f64() >= check_interval);
+            if due {
+                last_skew_check = Some(Instant::now());
+                match measure_clock_skew(&client, regions[0]).await {
+                    Ok(skew) if skew.abs() > max_skew => {
+                        if !skew_paused {
+                            report_error(format!(
+                                "CLOCK SKEW {:+.0}ms exceeds {:.0}ms tolerance, pausing capture until it recovers",
+                                skew, max_skew
+                            ));
+                        }
+                        skew_paused = true;
+                    }
+                    Ok(skew) => {
+                        if skew_paused {
+                            info!("Clock skew back to {:+.0}ms, resuming capture", skew);
+                        }
+                        skew_paused = false;
+                    }
+                    Err(e) => report_error(format!("Failed to check clock skew: {}", e)),
+                }
+            }
+            if skew_paused {
+                if final_pass || args.once {
+                    warn!("Skipping the last snapshot while the clock is skewed");
+                    break;
+                }
+                controls.wait(Duration::from_secs_f64(check_interval)).await;
+                continue;
+            }
+        }
+
+        // Binance reports the weight used so far this minute; near the limit, or during a
+        // 418/429 ban, wait it out instead of polling into a rejection
+        let throttle = apis
+            .iter()
+            .filter_map(|api| {
+                let delay = api
+                    .weight()
+                    .throttle_delay(weight_limit, args.weight_throttle_fraction)?;
+                Some((delay, api))
+            })
+            .max_by_key(|(delay, _)| *delay);
+        if let Some((delay, api)) = throttle {
+            if final_pass || args.once {
+                warn!("Skipping the last snapshot while request weight is exhausted");
+                last_pass = Some(PassOutcome::Failed(Failure::RateLimited));
+                break;
+            }
+            warn!(
+                "Request weight at {}/{} on {}, holding off for {:.1}s",
+                api.weight().used(),
+                weight_limit,
+                api.base_url(),
+                delay.as_secs_f64()
+            );
+            controls.wait(delay).await;
+            continue;
+        }
+
+        let iteration_start = Instant::now();
+
+        // Check if we should throttle to respect minimum interval
+        let time_since_last_snapshot = last_snapshot_time.elapsed().as_secs_f64();
+        if time_since_last_snapshot < MIN_INTERVAL_BETWEEN_SNAPSHOTS {
+            let sleep_duration =
+                Duration::from_secs_f64(MIN_INTERVAL_BETWEEN_SNAPSHOTS - time_since_last_snapshot);
+            sleep(sleep_duration).await;
+        }
+
+        // Update last snapshot time
+        last_snapshot_time = Instant::now();
+
+        let ctx = CaptureContext {
+            args: &args,
+            apis: &apis,
+            symbol_filters: &symbol_filters,
+            rotation: &rotation,
+            output_dir: &output_dir,
+            latest: latest.as_ref(),
+            subscribers: subscribers.as_ref(),
+            controls: &controls,
+        };
+        let slack = Duration::from_secs_f64(interval / 2.0);
+        let passes = captures
+            .iter_mut()
+            .filter_map(|capture_state| {
+                let due = capture_state.take_due(iteration_start, slack);
+                (forced || due).then_some(capture_state)
+            })
+            .map(|capture_state| capture_symbol(&ctx, capture_state, forced, iteration_start));
+        let mut passes = stream::iter(passes).buffer_unordered(args.max_concurrency.get());
+        let mut worst = None;
+        while let Some(result) = passes.next().await {
+            worst = worst.max(Some(result?));
+        }
+        last_pass = worst;
+        drop(passes);
+
+        // Buffered ndjson lines reach disk even while books are unchanged
+        for capture_state in &mut captures {
+            if let Some(writer) = &mut capture_state.ndjson {
+                if let Err(e) = writer.flush_if_due() {
+                    report_error(format!(
+                        "Error flushing {} ndjson: {}",
+                        capture_state.symbol, e
+                    ));
+                }
+            }
+        }
+
+        if final_pass || args.once {
+            break;
+        }
+
+        // Calculate if we need to sleep to maintain the desired interval
+        let elapsed = iteration_start.elapsed().as_secs_f64();
+        if let Ok(mut metrics) = metrics::METRICS.lock() {
+            metrics.iteration(elapsed);
+        }
+        if let Some(phase) = args.phase {
+            // Overruns skip to the next grid point rather than drifting off the grid
+            if elapsed >= interval {
+                warn!("Processing took longer than interval ({:.3}s)", elapsed);
+            }
+            controls.wait(next_grid_delay(interval, phase)).await;
+        } else if elapsed < interval {
+            let sleep_duration = Duration::from_secs_f64(interval - elapsed);
+            controls.wait(sleep_duration).await;
+        } else {
+            warn!("Processing took longer than interval ({:.3}s)", elapsed);
+        }
+    }
+
+    for capture_state in &mut captures {
+        if let Some(writer) = capture_state.ndjson.take() {
+            if let Err(e) = writer.close() {
+                report_error(format!(
+                    "Error closing {} ndjson: {}",
+                    capture_state.symbol, e
+                ));
+            }
+        }
+    }
+    let written: u64 = captures.iter().map(|c| c.stats.snapshots).sum();
+    info!(
+        "Stopped after writing {} snapshots in {:.1}s",
+        written,
+        capture_start.elapsed().as_secs_f64()
+    );
+    drop(subscribers); // Closes the WebSocket clients
+    let servers = metrics_server
+        .into_iter()
+        .chain(snapshot_server)
+        .chain(push_server);
+    for (shutdown, server) in servers {
+        let _ = shutdown.send(());
+        let _ = server.await;
+    }
+
+    let (error_count, mirror_failures) = DIAGNOSTICS
+        .lock()
+        .map(|d| (d.error_count, d.mirror_failures))
+        .unwrap_or_default();
+    for capture_state in &captures {
+        let summary = capture_state.stats.append_summary(
+            &capture_state.symbol,
+            &output_dir.borrow(),
+            error_count,
+            mirror_failures,
+        );
+        match summary {
+            Ok(filename) => info!("Session summary appended to {}", filename),
+            Err(e) => report_error(format!("Error writing session summary: {}", e)),
+        }
+    }
+    if !args.once {
+        return Ok(ExitCode::SUCCESS);
+    }
+    // No pass at all (Ctrl+C, clock skew, weight exhausted) counts as nothing saved
+    Ok(last_pass.unwrap_or(PassOutcome::NotSaved).exit_code())
+}
+
+// A flag, config file or symbol that can't work as given; exits with 2, the same as
+// clap's own usage errors
+#[derive(Debug)]
+struct InvalidConfig(String);
+
+impl std::fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for InvalidConfig {}
+
+// Why the process failed, least to most severe: a known cause outranks an unknown
+// one, and one a retry won't fix outranks a transient one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Failure {
+    Generic,
+    Network,     // Connection refused, dropped or timed out
+    RateLimited, // 429 over the request weight, or 418 banned
+    Invalid,     // Bad flags, config, symbol or a region that refuses this location
+}
+
+impl Failure {
+    // Classified by the first error in the source chain that says what went wrong
+    fn of(error: &(dyn Error + 'static)) -> Failure {
+        let mut current = Some(error);
+        while let Some(error) = current {
+            if error.is::<InvalidConfig>() {
+                return Failure::Invalid;
+            }
+            if let Some(e) = error.downcast_ref::<ApiError>() {
+                return match e {
+                    ApiError::RateLimited { .. } => Failure::RateLimited,
+                    ApiError::Timeout { .. } => Failure::Network,
+                    ApiError::RegionBlocked { .. } => Failure::Invalid,
+                    ApiError::Http { status, .. } if *status == StatusCode::BAD_REQUEST => {
+                        Failure::Invalid
+                    }
+                    // -1003 is too many requests; -11xx are bad request parameters, such
+                    // as -1121 Invalid symbol
+                    ApiError::Binance { code: -1003, .. } => Failure::RateLimited,
+                    ApiError::Binance {
+                        code: -1199..=-1100,
+                        ..
+                    } => Failure::Invalid,
+                    _ => Failure::Generic,
+                };
+            }
+            if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+                if e.is_connect() || e.is_timeout() {
+                    return Failure::Network;
+                }
+            }
+            current = error.source();
+        }
+        Failure::Generic
+    }
+
+    fn exit_code(self) -> ExitCode {
+        ExitCode::from(match self {
+            Failure::Generic => 1,
+            Failure::Invalid => 2,
+            Failure::RateLimited => 3,
+            Failure::Network => 4,
+        })
+    }
+}
+
+// How one symbol's pass ended, least to most severe. `--once` exits with the worst
+// across symbols.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum PassOutcome {
+    Saved,
+    NotSaved, // Fetched, but skipped or the save failed
+    Failed(Failure),
+}
+
+impl PassOutcome {
+    fn exit_code(self) -> ExitCode {
+        match self {
+            PassOutcome::Saved => ExitCode::SUCCESS,
+            PassOutcome::NotSaved => Failure::Generic.exit_code(),
+            PassOutcome::Failed(failure) => failure.exit_code(),
+        }
+    }
+}
+
+// Fetch and save one symbol's snapshot. The symbols of a pass run concurrently, so a
+// slow response for one pair doesn't hold up the others.
+async fn capture_symbol(
+    ctx: &CaptureContext<'_>,
+    capture_state: &mut SymbolCapture,
+    forced: bool,
+    iteration_start: Instant,
+) -> Result<PassOutcome, Box<dyn Error>> {
+    let output_dir = ctx.output_dir.borrow().clone();
+    let symbol = &capture_state.symbol;
+
+    // Execute both API calls in parallel, racing regions when redundancy is enabled
+    let fetch = async {
+        match &capture_state.stream {
+            Some(stream) => {
+                let (api, depth_limit) = (&ctx.apis[0], capture_state.depth_limit);
+                let (with_24h, on_parse_error) = (ctx.args.with_24h, ctx.args.on_parse_error);
+                fetch_from_stream(api, stream, symbol, depth_limit, with_24h, on_parse_error).await
+            }
+            None => {
+                let depth_limit = capture_state.depth_limit;
+                let (with_24h, on_parse_error) = (ctx.args.with_24h, ctx.args.on_parse_error);
+                fetch_redundant(ctx.apis, symbol, depth_limit, with_24h, on_parse_error).await
+            }
+        }
+    };
+    let fetched = fetch
+        .instrument(info_span!(
+            "fetch",
+            symbol = %symbol,
+            timestamp = %Local::now().format("%H:%M:%S%.3f")
+        ))
+        .await;
+    let fetch_latency = fetched.latency;
+    // Kept until a snapshot is actually saved, in case this read gets skipped
+    capture_state.resync_pending |= fetched.resync;
+    // A failed 24h request costs only the ticker_24h field, not the snapshot
+    let ticker_24h = match fetched.ticker_24h {
+        Some(Ok(ticker)) => Some(ticker),
+        Some(Err(e)) => {
+            report_error(format!("Failed to get 24h ticker for {}: {}", symbol, e));
+            if let Ok(mut metrics) = metrics::METRICS.lock() {
+                metrics.fetch_error(symbol, "ticker_24h");
+            }
+            None
+        }
+        None => None,
+    };
+    if let Ok(mut metrics) = metrics::METRICS.lock() {
+        // A streamed book isn't fetched, so only its price request has a latency
+        if capture_state.stream.is_none() {
+            metrics.fetch_latency(symbol, "orderbook", fetch_latency.orderbook / 1000.0);
+        }
+        metrics.fetch_latency(symbol, "price", fetch_latency.price / 1000.0);
+    }
+
+    let outcome = match (fetched.orderbook, fetched.price) {
+        (Ok(snapshot), Ok(price_data)) => {
+            let mut outcome = PassOutcome::NotSaved;
+            if let Ok(mut diagnostics) = DIAGNOSTICS.lock() {
+                diagnostics.last_snapshot = Some(snapshot.clone());
+            }
+
+            let mid = book_mid(&snapshot);
+            let mid_undefined = mid.is_none();
+
+            // Percent move of the mid since the last accepted snapshot
+            let tick_move = match (capture_state.last_mid, &mid) {
+                (Some(last), Some(mid)) => Some(((mid - last) / last * 100.0).abs()),
+                _ => None,
+            };
+            let outlier = ctx
+                .args
+                .max_tick_move
+                .map(|max| tick_move.is_some_and(|tick_move| tick_move > max));
+            if outlier != Some(true) {
+                if let Some(mid) = mid {
+                    capture_state.last_mid = Some(mid);
+                }
+            }
+
+            let duplicate = ctx.args.skip_unchanged
+                && capture_state
+                    .last_saved
+                    .as_ref()
+                    .is_some_and(|(id, price)| {
+                        *id == snapshot.last_update_id && *price == price_data.price
+                    });
+
+            let invalid = validate_orderbook(&snapshot).err();
+
+            if fetched.book_unchanged && !forced {
+                info!("Order book unchanged since last fetch, skipping save");
+            } else if duplicate && !forced {
+                info!("{} unchanged, skipped", symbol);
+            } else if mid_undefined && ctx.args.nan_policy == NanPolicy::Skip {
+                report_error("Skipping snapshot: book has an empty side".to_string());
+            } else if let (Some(e), false) = (&invalid, ctx.args.quarantine) {
+                report_error(format!("Skipping invalid {} book: {}", symbol, e));
+            } else if outlier == Some(true) && ctx.args.drop_outliers {
+                report_error(format!(
+                    "Dropping {} snapshot: mid moved {:.3}% in one interval",
+                    symbol,
+                    tick_move.unwrap_or_default()
+                ));
+            } else {
+                if outlier == Some(true) {
+                    report_error(format!(
+                        "Flagging {} snapshot as an outlier: mid moved {:.3}% in one interval",
+                        symbol,
+                        tick_move.unwrap_or_default()
+                    ));
+                }
+                // A number is used up even if the save fails, so check-sequence shows the loss
+                let capture = CaptureInfo {
+                    fetch_latency,
+                    source: fetched.source,
+                    sequence: capture_state.sequence.advance(),
+                    depth_limit: capture_state.depth_limit,
+                    outlier,
+                    layered: ctx.args.layered,
+                    format: ctx.args.format,
+                    compress: ctx.args.compress,
+                    nan_policy: ctx.args.nan_policy,
+                    imbalance_weight: ctx.args.imbalance_weight,
+                    canonicalize: ctx.args.canonicalize,
+                    save_levels: ctx.args.save_levels.map(NonZeroUsize::get),
+                    price_bins: ctx.args.price_bins.map(NonZeroUsize::get),
+                    price_bin_span_bps: ctx.args.price_bin_span_bps,
+                    cumulative: ctx.args.cumulative,
+                    trim_to_precision: ctx.args.trim_to_precision,
+                    resync: std::mem::take(&mut capture_state.resync_pending),
+                };
+                if let Err(e) = capture_state.sequence.persist() {
+                    report_error(format!("Error updating sequence state: {}", e));
+                }
+
+                // Retried after waiting out a full disk or switching to the fallback
+                let mut retrying = false;
+                loop {
+                    // --on-disk-full fallback may have moved the output since the last try
+                    let output_dir = ctx.output_dir.borrow().clone();
+                    let (snapshot_dir, mirror_dir);
+                    let destination = match &mut capture_state.ndjson {
+                        // Kept apart as plain files, outside any part_NNNNN rotation and the mirror
+                        _ if invalid.is_some() => {
+                            report_error(format!(
+                                "Quarantining invalid {} book: {}",
+                                symbol,
+                                invalid
+                                    .as_ref()
+                                    .map(ToString::to_string)
+                                    .unwrap_or_default()
+                            ));
+                            snapshot_dir = format!("{}/{}", output_dir, QUARANTINE_DIR);
+                            Destination::Files {
+                                output_dir: &snapshot_dir,
+                                mirror_dir: None,
+                            }
+                        }
+                        // ndjson files live directly in the output directory and aren't mirrored
+                        Some(writer) => Destination::Ndjson(writer),
+                        None => {
+                            let mut rotation = ctx.rotation.borrow_mut();
+                            snapshot_dir = rotation.next_dir(&output_dir);
+                            // Same part_NNNNN layout under the mirror as under the primary
+                            mirror_dir = ctx
+                                .args
+                                .mirror_dir
+                                .as_deref()
+                                .map(|dir| rotation.next_dir(dir));
+                            Destination::Files {
+                                output_dir: &snapshot_dir,
+                                mirror_dir: mirror_dir.as_deref(),
+                            }
+                        }
+                    };
+                    // Quarantined books go out in full and don't become the delta base
+                    let delta_base = capture_state
+                        .delta_base
+                        .as_ref()
+                        .filter(|_| invalid.is_none());
+                    let saved = async {
+                        let filters = ctx.symbol_filters.get(symbol);
+                        let full = || {
+                            combined_data(
+                                &snapshot,
+                                &price_data,
+                                ticker_24h.as_ref(),
+                                &capture,
+                                filters,
+                            )
+                        };
+                        // Served before it's written; quarantined books aren't served at all,
+                        // and a retried save was already served
+                        let publishing = ctx.publishing() && invalid.is_none() && !retrying;
+                        match delta_base {
+                            Some(base) => {
+                                if publishing {
+                                    ctx.publish(symbol, &full()?);
+                                }
+                                save_delta(
+                                    base,
+                                    &snapshot,
+                                    &price_data,
+                                    ticker_24h.as_ref(),
+                                    &capture,
+                                    symbol,
+                                    destination,
+                                )
+                            }
+                            None => {
+                                let combined = full()?;
+                                if publishing {
+                                    ctx.publish(symbol, &combined);
+                                }
+                                save_snapshot(combined, &capture, symbol, destination, filters)
+                                    .await
+                            }
+                        }
+                    };
+                    match saved
+                        .instrument(info_span!(
+                            "save",
+                            symbol = %symbol,
+                            sequence = capture.sequence
+                        ))
+                        .await
+                    {
+                        Ok(filename) => {
+                            outcome = PassOutcome::Saved;
+                            if ctx.args.output == Output::Delta && invalid.is_none() {
+                                capture_state.delta_base = Some(stored_book(&snapshot, &capture));
+                            }
+                            capture_state.last_saved =
+                                Some((snapshot.last_update_id, price_data.price.clone()));
+                            ctx.rotation.borrow_mut().record_saved();
+                            if let Ok(mut metrics) = metrics::METRICS.lock() {
+                                metrics.snapshot_saved(symbol);
+                            }
+                            capture_state.stats.record_saved(&snapshot, &capture);
+                            let saved = capture_state.stats.snapshots;
+                            if saved.is_multiple_of(ctx.args.log_sample) {
+                                info!(
+                                    symbol = %symbol,
+                                    file = %filename,
+                                    duration_ms = iteration_start.elapsed().as_millis() as u64,
+                                    saved,
+                                    "Snapshot saved"
+                                );
+                            }
+                        }
+                        Err(e) if is_disk_full(e.as_ref()) => {
+                            report_error(format!(
+                                "Output disk full writing to {}: {}",
+                                output_dir, e
+                            ));
+                            match ctx.args.on_disk_full {
+                                DiskFullPolicy::Pause => {
+                                    if wait_for_free_space(&output_dir, ctx.controls).await {
+                                        retrying = true;
+                                        continue;
+                                    }
+                                    warn!("Dropping the {} snapshot: shutting down", symbol);
+                                }
+                                DiskFullPolicy::Fallback if output_dir != FALLBACK_OUTPUT_DIR => {
+                                    warn!("Switching output to {}", FALLBACK_OUTPUT_DIR);
+                                    *ctx.output_dir.borrow_mut() = FALLBACK_OUTPUT_DIR.to_string();
+                                    retrying = true;
+                                    continue;
+                                }
+                                _ => {
+                                    return Err(format!(
+                                        "Output disk full, stopping capture: {}",
+                                        e
+                                    )
+                                    .into())
+                                }
+                            }
+                        }
+                        Err(e) => report_error(format!("Error saving snapshot: {}", e)),
+                    }
+                    break;
+                }
+            }
+
+            if let Some(window) = ctx.args.footprint_window {
+                capture_state.footprint.record(&snapshot);
+                if capture_state.footprint.is_complete(window) {
+                    let aggregation = ctx.args.footprint_aggregation;
+                    match capture_state
+                        .footprint
+                        .save(symbol, &output_dir, aggregation)
+                        .await
+                    {
+                        Ok(filename) => info!("Footprint saved to {}", filename),
+                        Err(e) => report_error(format!("Error saving footprint: {}", e)),
+                    }
+                    capture_state.footprint = Footprint::new()?;
+                }
+            }
+
+            if ctx.args.liquidity_csv {
+                let (distances, nan_policy) = (&ctx.args.liquidity_bps, ctx.args.nan_policy);
+                let row =
+                    append_liquidity_row(&snapshot, symbol, &output_dir, distances, nan_policy);
+                if let Err(e) = row.await {
+                    report_error(format!("Error writing liquidity row: {}", e));
+                }
+            }
+            outcome
+        }
+        (orderbook, price) => {
+            let orderbook_failure = orderbook.as_ref().err().map(|e| Failure::of(e.as_ref()));
+            let price_failure = price.as_ref().err().map(|e| Failure::of(e.as_ref()));
+            let worst = orderbook_failure
+                .max(price_failure)
+                .unwrap_or(Failure::Generic);
+            let outcome = PassOutcome::Failed(worst);
+            if let Ok(mut metrics) = metrics::METRICS.lock() {
+                if orderbook.is_err() {
+                    metrics.fetch_error(symbol, "orderbook");
+                }
+                if price.is_err() {
+                    metrics.fetch_error(symbol, "price");
+                }
+            }
+            if let Err(e) = orderbook {
+                save_unparseable_body(e.as_ref(), &output_dir, ctx.args.on_parse_error);
+                report_error(format!("Failed to get orderbook snapshot: {}", e));
+            }
+            if let Err(e) = price {
+                save_unparseable_body(e.as_ref(), &output_dir, ctx.args.on_parse_error);
+                report_error(format!("Failed to get price data: {}", e));
+            }
+            outcome
+        }
+    };
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_map_to_exit_codes_by_cause() {
+        let invalid: Box<dyn Error> = Box::new(InvalidConfig("bad symbol".to_string()));
+        assert_eq!(Failure::of(invalid.as_ref()), Failure::Invalid);
+        let banned = ApiError::RateLimited {
+            context: "fetching order book",
+            retry_after: None,
+        };
+        assert_eq!(Failure::of(&banned), Failure::RateLimited);
+        let unknown_symbol = ApiError::Binance {
+            context: "fetching order book",
+            code: -1121,
+            msg: "Invalid symbol.".to_string(),
+        };
+        assert_eq!(Failure::of(&unknown_symbol), Failure::Invalid);
+        let other: Box<dyn Error> = "disk full".into();
+        assert_eq!(Failure::of(other.as_ref()), Failure::Generic);
+        assert!(Failure::Invalid > Failure::RateLimited);
+    }
+
+    fn book(last_update_id: u64, bid: &str) -> OrderBook {
+        serde_json::from_value(serde_json::json!({
+            "lastUpdateId": last_update_id,
+            "bids": [[bid, "1.0"]],
+            "asks": [["2.0", "1.0"]]
+        }))
+        .unwrap()
+    }
+
+    fn capture(sequence: u64) -> CaptureInfo {
+        CaptureInfo {
+            fetch_latency: FetchLatency {
+                orderbook: 0.0,
+                price: 0.0,
+            },
+            source: "test".to_string(),
+            sequence,
+            depth_limit: 5,
+            outlier: None,
+            layered: false,
+            format: OutputFormat::Json,
+            compress: false,
+            nan_policy: NanPolicy::Null,
+            imbalance_weight: None,
+            canonicalize: false,
+            save_levels: None,
+            price_bins: None,
+            price_bin_span_bps: PRICE_BIN_SPAN_BPS,
+            cumulative: false,
+            trim_to_precision: false,
+            resync: false,
+        }
+    }
+
+    fn scratch_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn phase_must_be_a_fraction_of_the_interval() {
+        let phase = |value: &str| {
+            Args::try_parse_from(["binance_price_checker", "--phase", value]).map(|a| a.phase)
+        };
+        assert_eq!(phase("0").unwrap(), Some(0.0));
+        assert_eq!(phase("0.25").unwrap(), Some(0.25));
+        assert!(phase("1").is_err());
+        assert!(phase("-0.5").is_err());
+    }
+
+    #[test]
+    fn unparsable_schedule_is_rejected() {
+        let windows = |value: &str| {
+            let args = Args::try_parse_from(["binance_price_checker", "--schedule", value])?;
+            Ok::<_, clap::Error>(args.schedule.map(|schedule| schedule.windows.len()))
+        };
+        assert_eq!(windows("09:30-12:00,13:00-16:00 -05:00").unwrap(), Some(2));
+        assert!(windows("13:30-25:00 UTC").is_err());
+        assert!(windows("13:30 UTC").is_err());
+        assert!(windows("13:30-20:00 Mars").is_err());
+    }
+
+    #[test]
+    fn deltas_in_the_same_second_get_their_own_files() {
+        let dir = scratch_dir("deltas_same_second");
+        let price = PriceData {
+            price: "1.5".to_string(),
+            timestamp: 0,
+        };
+        let base = book(1, "1.0");
+        let start = Instant::now();
+        let mut files = Vec::new();
+        for (sequence, bid) in [(1, "1.1"), (2, "1.2")] {
+            let destination = Destination::Files {
+                output_dir: &dir,
+                mirror_dir: None,
+            };
+            let orderbook = book(sequence + 1, bid);
+            let saved = save_delta(
+                &base,
+                &orderbook,
+                &price,
+                None,
+                &capture(sequence),
+                "SUIUSDT",
+                destination,
+            );
+            files.push(saved.unwrap());
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_ne!(files[0], files[1]);
+        assert!(files.iter().all(|file| Path::new(file).exists()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn snapshots_in_the_same_second_get_their_own_files() {
+        let dir = scratch_dir("snapshots_same_second");
+        let price = PriceData {
+            price: "1.5".to_string(),
+            timestamp: 0,
+        };
+        let orderbook = book(1, "1.0");
+        let mut files = Vec::new();
+        for sequence in [7, 8] {
+            let capture = capture(sequence);
+            let mut data = combined_data(&orderbook, &price, None, &capture, None).unwrap();
+            data.local_datetime = "2026-01-02 03:04:05".to_string();
+            let destination = Destination::Files {
+                output_dir: &dir,
+                mirror_dir: None,
+            };
+            let saved = save_snapshot(data, &capture, "SUIUSDT", destination, None).await;
+            files.push(saved.unwrap());
+        }
+        assert_eq!(
+            files,
+            [7, 8].map(|sequence| format!(
+                "{}/orderbook_SUIUSDT_20260102_030405_{}.json",
+                dir, sequence
+            ))
+        );
+        assert!(files.iter().all(|file| Path::new(file).exists()));
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,170 @@
+// `--metrics-addr`: counters and latency histograms for the capture loop, served
+// in the Prometheus text format on `GET /metrics`. The loop updates METRICS; the
+// server only renders it.
+
+use axum::{routing::get, Router};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+// Upper bounds in seconds, shared by both histograms
+const BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+pub static METRICS: Mutex<Metrics> = Mutex::new(Metrics {
+    snapshots_saved: BTreeMap::new(),
+    fetch_errors: BTreeMap::new(),
+    fetch_latency: BTreeMap::new(),
+    iteration_seconds: Histogram::new(),
+});
+
+pub struct Metrics {
+    snapshots_saved: BTreeMap<String, u64>, // By symbol
+    fetch_errors: BTreeMap<(String, &'static str), u64>, // By symbol and request
+    fetch_latency: BTreeMap<(String, &'static str), Histogram>, // By symbol and request
+    iteration_seconds: Histogram,
+}
+
+#[derive(Clone)]
+struct Histogram {
+    counts: [u64; BUCKETS.len()], // Per bucket, not cumulative; rendering sums them
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    const fn new() -> Histogram {
+        Histogram {
+            counts: [0; BUCKETS.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.counts[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(self.counts) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, separator, self.count
+        );
+        let braces = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, braces, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, braces, self.count);
+    }
+}
+
+impl Metrics {
+    pub fn snapshot_saved(&mut self, symbol: &str) {
+        *self.snapshots_saved.entry(symbol.to_string()).or_default() += 1;
+    }
+
+    /// `request` is "orderbook" or "price".
+    pub fn fetch_error(&mut self, symbol: &str, request: &'static str) {
+        *self
+            .fetch_errors
+            .entry((symbol.to_string(), request))
+            .or_default() += 1;
+    }
+
+    pub fn fetch_latency(&mut self, symbol: &str, request: &'static str, seconds: f64) {
+        self.fetch_latency
+            .entry((symbol.to_string(), request))
+            .or_insert_with(Histogram::new)
+            .observe(seconds);
+    }
+
+    pub fn iteration(&mut self, seconds: f64) {
+        self.iteration_seconds.observe(seconds);
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP orderbook_snapshots_saved_total Snapshots written to disk.\n");
+        out.push_str("# TYPE orderbook_snapshots_saved_total counter\n");
+        for (symbol, count) in &self.snapshots_saved {
+            let _ = writeln!(
+                out,
+                "orderbook_snapshots_saved_total{{symbol=\"{}\"}} {}",
+                symbol, count
+            );
+        }
+
+        out.push_str("# HELP orderbook_fetch_errors_total Failed order book or price fetches.\n");
+        out.push_str("# TYPE orderbook_fetch_errors_total counter\n");
+        for ((symbol, request), count) in &self.fetch_errors {
+            let _ = writeln!(
+                out,
+                "orderbook_fetch_errors_total{{symbol=\"{}\",request=\"{}\"}} {}",
+                symbol, request, count
+            );
+        }
+
+        out.push_str("# HELP orderbook_fetch_latency_seconds Round trip of each fetch request.\n");
+        out.push_str("# TYPE orderbook_fetch_latency_seconds histogram\n");
+        for ((symbol, request), histogram) in &self.fetch_latency {
+            let labels = format!("symbol=\"{}\",request=\"{}\"", symbol, request);
+            histogram.render(&mut out, "orderbook_fetch_latency_seconds", &labels);
+        }
+
+        out.push_str(
+            "# HELP orderbook_iteration_seconds Time to fetch and save every symbol once.\n",
+        );
+        out.push_str("# TYPE orderbook_iteration_seconds histogram\n");
+        self.iteration_seconds
+            .render(&mut out, "orderbook_iteration_seconds", "");
+        out
+    }
+}
+
+async fn metrics() -> String {
+    METRICS
+        .lock()
+        .map(|metrics| metrics.render())
+        .unwrap_or_default()
+}
+
+/// Bind `addr` and serve /metrics until `shutdown` fires (or its sender is dropped).
+pub async fn serve(
+    addr: SocketAddr,
+    shutdown: oneshot::Receiver<()>,
+) -> std::io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving metrics on http://{}/metrics", addr);
+    let app = Router::new().route("/metrics", get(metrics));
+    Ok(tokio::spawn(async move {
+        let server = axum::serve(listener, app).with_graceful_shutdown(async {
+            let _ = shutdown.await;
+        });
+        if let Err(e) = server.await {
+            error!("Metrics server failed: {}", e);
+        }
+    }))
+}
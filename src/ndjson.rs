@@ -0,0 +1,173 @@
+// `--output-mode ndjson`: every snapshot of a symbol is appended as one compact
+// CombinedData object per line to `orderbook_<symbol>.ndjson`, instead of a file
+// per tick. Lines are buffered and flushed every FLUSH_INTERVAL and on shutdown.
+// agg-trades writes its `aggtrades_<symbol>.ndjson` files the same way.
+//
+// With --max-file-size, a file that would grow past the limit is renamed to
+// `orderbook_<symbol>_<YYYYmmdd_HHMMSS>.ndjson` and a fresh one is started.
+//
+// With --compress the files end in `.ndjson.gz`. Each run (and each rotation)
+// writes its own gzip member, so appending across restarts still yields a valid
+// multi-member file that `gzip -d` and flate2's MultiGzDecoder read as one stream.
+// The size limit then counts bytes before compression.
+
+use chrono::Local;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct NdjsonWriter {
+    output_dir: String,
+    name: String, // File name without the extension, e.g. orderbook_SUIUSDT
+    path: String,
+    writer: Sink,
+    bytes: u64, // Size of the current file including buffered lines
+    max_bytes: Option<u64>,
+    compress: bool,
+    last_flush: Instant,
+}
+
+enum Sink {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl Sink {
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        match self {
+            Sink::Plain(writer) => writer.write_all(data),
+            Sink::Gzip(encoder) => encoder.write_all(data),
+        }
+    }
+
+    // A gzip flush is a sync flush: everything so far is decodable, the member stays open
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(writer) => writer.flush(),
+            Sink::Gzip(encoder) => encoder.flush(),
+        }
+    }
+
+    // Write the gzip trailer so the file ends on a complete member; nothing may be
+    // written afterwards
+    fn finish(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(writer) => writer.flush(),
+            Sink::Gzip(encoder) => {
+                encoder.try_finish()?;
+                encoder.get_mut().flush()
+            }
+        }
+    }
+}
+
+impl NdjsonWriter {
+    /// Open (or continue appending to) `<name>.ndjson` in `output_dir`.
+    pub fn open(
+        output_dir: &str,
+        name: &str,
+        max_bytes: Option<u64>,
+        compress: bool,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(output_dir)?;
+        let path = format!("{}/{}.{}", output_dir, name, extension(compress));
+        let (writer, bytes) = open_append(&path, compress)?;
+        Ok(NdjsonWriter {
+            output_dir: output_dir.to_string(),
+            name: name.to_string(),
+            path,
+            writer,
+            bytes,
+            max_bytes,
+            compress,
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// Append one serialized snapshot as a line; returns the file it went to.
+    pub fn append(&mut self, line: &[u8]) -> io::Result<&str> {
+        let len = line.len() as u64 + 1;
+        if let Some(max_bytes) = self.max_bytes {
+            // A line larger than the limit still goes into a file of its own
+            if self.bytes > 0 && self.bytes + len > max_bytes {
+                self.rotate()?;
+            }
+        }
+        self.writer.write_all(line)?;
+        self.writer.write_all(b"\n")?;
+        self.bytes += len;
+        self.flush_if_due()?;
+        Ok(&self.path)
+    }
+
+    /// Flush buffered lines if FLUSH_INTERVAL has passed since the last flush.
+    pub fn flush_if_due(&mut self) -> io::Result<()> {
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        self.writer.flush()
+    }
+
+    /// Flush and, for gzip, end the member so the file is complete on disk.
+    pub fn close(mut self) -> io::Result<()> {
+        self.writer.finish()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.finish()?;
+
+        let timestamp = Local::now().format("%Y%m%d_%H%M%S");
+        let extension = extension(self.compress);
+        let mut rotated = format!(
+            "{}/{}_{}.{}",
+            self.output_dir, self.name, timestamp, extension
+        );
+        // Several rotations within one second get a numeric suffix
+        let mut suffix = 1;
+        while Path::new(&rotated).exists() {
+            rotated = format!(
+                "{}/{}_{}_{}.{}",
+                self.output_dir, self.name, timestamp, suffix, extension
+            );
+            suffix += 1;
+        }
+        fs::rename(&self.path, &rotated)?;
+        info!("Rotated {} to {}", self.path, rotated);
+
+        let (writer, bytes) = open_append(&self.path, self.compress)?;
+        self.writer = writer;
+        self.bytes = bytes;
+        Ok(())
+    }
+}
+
+fn extension(compress: bool) -> &'static str {
+    if compress {
+        "ndjson.gz"
+    } else {
+        "ndjson"
+    }
+}
+
+fn open_append(path: &str, compress: bool) -> io::Result<(Sink, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let bytes = file.metadata()?.len();
+    let writer = BufWriter::new(file);
+    let sink = if compress {
+        Sink::Gzip(GzEncoder::new(writer, Compression::default()))
+    } else {
+        Sink::Plain(writer)
+    };
+    Ok((sink, bytes))
+}
@@ -0,0 +1,386 @@
+use binance_price_checker::binfixed;
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const USAGE: &str = "Usage: resample DIR --interval <1s|500ms|1m> \
+    [--method <last|mean|vwap>] [--out OUT_DIR] [--tradingview BARS.csv]";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PriceData {
+    price: String,
+    timestamp: u64,
+}
+
+// Snapshot as written by main.rs; fields this tool doesn't touch are carried through as-is
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Snapshot {
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: u64,
+    bids: Vec<[String; 2]>,
+    asks: Vec<[String; 2]>,
+    current_price: PriceData,
+    local_timestamp: u64,
+    local_datetime: String,
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Method {
+    Last,
+    Mean,
+    Vwap,
+}
+
+impl Method {
+    fn parse(s: &str) -> Result<Method, Box<dyn Error>> {
+        match s {
+            "last" => Ok(Method::Last),
+            "mean" => Ok(Method::Mean),
+            "vwap" => Ok(Method::Vwap),
+            other => Err(format!("Unknown method '{}', expected last|mean|vwap", other).into()),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Method::Last => "last",
+            Method::Mean => "mean",
+            Method::Vwap => "vwap",
+        }
+    }
+}
+
+struct Args {
+    input_dir: String,
+    output_dir: String,
+    interval_ms: u64,
+    method: Method,
+    // Write OHLC bars of the mid price instead of resampled snapshots
+    tradingview: Option<String>,
+}
+
+// Accepts "500ms", "1s", "5m", "1h" or a bare number of seconds
+fn parse_interval_ms(s: &str) -> Result<u64, Box<dyn Error>> {
+    let (value, scale) = if let Some(v) = s.strip_suffix("ms") {
+        (v, 1.0)
+    } else if let Some(v) = s.strip_suffix('s') {
+        (v, 1000.0)
+    } else if let Some(v) = s.strip_suffix('m') {
+        (v, 60_000.0)
+    } else if let Some(v) = s.strip_suffix('h') {
+        (v, 3_600_000.0)
+    } else {
+        (s, 1000.0)
+    };
+    let ms = (value.parse::<f64>()? * scale).round();
+    if ms < 1.0 {
+        return Err(format!("Interval '{}' must be at least 1ms", s).into());
+    }
+    Ok(ms as u64)
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut input_dir = None;
+    let mut output_dir = None;
+    let mut interval_ms = None;
+    let mut method = Method::Last;
+    let mut tradingview = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interval" => {
+                interval_ms = Some(parse_interval_ms(&args.next().ok_or(USAGE)?)?);
+            }
+            "--method" => method = Method::parse(&args.next().ok_or(USAGE)?)?,
+            "--out" => output_dir = Some(args.next().ok_or(USAGE)?),
+            "--tradingview" => tradingview = Some(args.next().ok_or(USAGE)?),
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if input_dir.is_none() => input_dir = Some(arg),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, USAGE).into()),
+        }
+    }
+
+    let input_dir = input_dir.ok_or(USAGE)?;
+    let interval_ms = interval_ms.ok_or(USAGE)?;
+    let output_dir = output_dir.unwrap_or_else(|| {
+        format!(
+            "{}/resampled_{}ms_{}",
+            input_dir,
+            interval_ms,
+            method.name()
+        )
+    });
+
+    Ok(Args {
+        input_dir,
+        output_dir,
+        interval_ms,
+        method,
+        tradingview,
+    })
+}
+
+// Symbol from an `orderbook_<SYMBOL>_<timestamp>.{json,bin}` filename (symbols never contain '_')
+fn symbol_from_filename(name: &str) -> Option<String> {
+    let stem = name.strip_prefix("orderbook_")?;
+    let stem = stem
+        .strip_suffix(".json")
+        .or_else(|| stem.strip_suffix(".bin"))?;
+    let (symbol, _timestamp) = stem.split_once('_')?;
+    Some(symbol.to_string())
+}
+
+// Files in `dir` plus those in main.rs's `part_NNNNN` rotation subdirectories
+fn snapshot_paths(dir: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_part = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("part_"));
+        if is_part && path.is_dir() {
+            for part_entry in fs::read_dir(&path)? {
+                paths.push(part_entry?.path());
+            }
+        } else {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+fn read_snapshot(path: &Path) -> Result<Snapshot, Box<dyn Error>> {
+    if path.extension().is_some_and(|ext| ext == "bin") {
+        return Ok(snapshot_from_binfixed(binfixed::decode(&fs::read(path)?)?));
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+// binfixed keeps only the book, price and timestamps; the rest is rebuilt or left out
+fn snapshot_from_binfixed(book: binfixed::FixedBook) -> Snapshot {
+    let local_datetime = Local
+        .timestamp_opt(book.local_timestamp as i64, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+    Snapshot {
+        last_update_id: book.last_update_id,
+        bids: book.bids,
+        asks: book.asks,
+        current_price: PriceData {
+            price: book.price,
+            timestamp: book.price_timestamp,
+        },
+        local_timestamp: book.local_timestamp,
+        local_datetime,
+        extra: serde_json::Map::new(),
+    }
+}
+
+fn load_snapshots(dir: &str) -> Result<(String, Vec<Snapshot>), Box<dyn Error>> {
+    let mut symbol = None;
+    let mut snapshots = Vec::new();
+
+    for path in snapshot_paths(dir)? {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some(file_symbol) = symbol_from_filename(name) else {
+            continue;
+        };
+
+        match read_snapshot(&path) {
+            Ok(snapshot) => {
+                symbol.get_or_insert(file_symbol);
+                snapshots.push(snapshot);
+            }
+            Err(e) => eprintln!("Skipping {}: {}", path.display(), e),
+        }
+    }
+
+    snapshots.sort_by_key(|s| s.current_price.timestamp);
+    let symbol = symbol.ok_or_else(|| format!("No snapshot files found in {}", dir))?;
+    Ok((symbol, snapshots))
+}
+
+// Mid from the top of book, falling back to the recorded ticker price for one-sided books
+fn mid_price(snapshot: &Snapshot) -> Option<f64> {
+    match (snapshot.bids.first(), snapshot.asks.first()) {
+        (Some(bid), Some(ask)) => {
+            let bid: f64 = bid[0].parse().ok()?;
+            let ask: f64 = ask[0].parse().ok()?;
+            Some((bid + ask) / 2.0)
+        }
+        _ => snapshot.current_price.price.parse().ok(),
+    }
+}
+
+// Quantity resting at the best bid and best ask, used as the vwap weight
+fn top_of_book_qty(snapshot: &Snapshot) -> f64 {
+    let qty = |levels: &[[String; 2]]| {
+        levels
+            .first()
+            .and_then(|level| level[1].parse::<f64>().ok())
+            .unwrap_or(0.0)
+    };
+    qty(&snapshot.bids) + qty(&snapshot.asks)
+}
+
+fn aggregate_price(bucket: &[Snapshot], method: Method) -> Option<f64> {
+    match method {
+        Method::Last => bucket.last().and_then(mid_price),
+        Method::Mean => {
+            let mids: Vec<f64> = bucket.iter().filter_map(mid_price).collect();
+            if mids.is_empty() {
+                return None;
+            }
+            Some(mids.iter().sum::<f64>() / mids.len() as f64)
+        }
+        Method::Vwap => {
+            let (weighted, total_weight) =
+                bucket
+                    .iter()
+                    .fold((0.0, 0.0), |(weighted, total), snapshot| {
+                        match mid_price(snapshot) {
+                            Some(mid) => {
+                                let weight = top_of_book_qty(snapshot);
+                                (weighted + mid * weight, total + weight)
+                            }
+                            None => (weighted, total),
+                        }
+                    });
+            if total_weight > 0.0 {
+                Some(weighted / total_weight)
+            } else {
+                aggregate_price(bucket, Method::Mean)
+            }
+        }
+    }
+}
+
+// Represent each bucket by its last book, with the aggregated mid as the price
+fn resample_bucket(bucket: &[Snapshot], bucket_start_ms: u64, args: &Args) -> Option<Snapshot> {
+    let mut output = bucket.last()?.clone();
+    if args.method != Method::Last {
+        let price = aggregate_price(bucket, args.method)?;
+        output.current_price = PriceData {
+            price: format!("{:.8}", price),
+            timestamp: bucket_start_ms,
+        };
+    }
+    output.extra.insert(
+        "resample".to_string(),
+        serde_json::json!({
+            "method": args.method.name(),
+            "interval_ms": args.interval_ms,
+            "bucket_start_ms": bucket_start_ms,
+            "source_snapshots": bucket.len(),
+        }),
+    );
+    Some(output)
+}
+
+// Split time-sorted snapshots into consecutive `interval_ms` buckets; empty buckets are skipped
+fn bucket_snapshots(snapshots: &[Snapshot], interval_ms: u64) -> Vec<(u64, &[Snapshot])> {
+    let mut buckets = Vec::new();
+    let mut start = 0;
+    while start < snapshots.len() {
+        let bucket_start_ms = snapshots[start].current_price.timestamp / interval_ms * interval_ms;
+        let end = snapshots[start..]
+            .iter()
+            .position(|s| s.current_price.timestamp >= bucket_start_ms + interval_ms)
+            .map_or(snapshots.len(), |offset| start + offset);
+        buckets.push((bucket_start_ms, &snapshots[start..end]));
+        start = end;
+    }
+    buckets
+}
+
+// OHLC bars of the mid price as CSV in TradingView's import layout (unix seconds)
+fn write_tradingview_bars(
+    buckets: &[(u64, &[Snapshot])],
+    filename: &str,
+) -> Result<usize, Box<dyn Error>> {
+    let mut csv = String::from("time,open,high,low,close\n");
+    let mut written = 0;
+    for (bucket_start_ms, bucket) in buckets {
+        let mids: Vec<f64> = bucket.iter().filter_map(mid_price).collect();
+        let (Some(&open), Some(&close)) = (mids.first(), mids.last()) else {
+            continue;
+        };
+        let high = mids.iter().copied().fold(f64::MIN, f64::max);
+        let low = mids.iter().copied().fold(f64::MAX, f64::min);
+        csv.push_str(&format!(
+            "{},{:.8},{:.8},{:.8},{:.8}\n",
+            bucket_start_ms / 1000,
+            open,
+            high,
+            low,
+            close
+        ));
+        written += 1;
+    }
+    fs::write(filename, csv)?;
+    Ok(written)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = parse_args()?;
+    let (symbol, snapshots) = load_snapshots(&args.input_dir)?;
+    println!(
+        "Loaded {} snapshots for {} from {}",
+        snapshots.len(),
+        symbol,
+        args.input_dir
+    );
+
+    let buckets = bucket_snapshots(&snapshots, args.interval_ms);
+
+    if let Some(bars_file) = &args.tradingview {
+        let written = write_tradingview_bars(&buckets, bars_file)?;
+        println!(
+            "Wrote {} {}ms bars to {}",
+            written, args.interval_ms, bars_file
+        );
+        return Ok(());
+    }
+
+    if !Path::new(&args.output_dir).exists() {
+        fs::create_dir_all(&args.output_dir)?;
+    }
+
+    let mut written = 0;
+    for (bucket_start_ms, bucket) in buckets {
+        if let Some(output) = resample_bucket(bucket, bucket_start_ms, &args) {
+            let bucket_time = Local
+                .timestamp_millis_opt(bucket_start_ms as i64)
+                .single()
+                .ok_or("Snapshot timestamp out of range")?;
+            let filename = format!(
+                "{}/orderbook_{}_{}.json",
+                args.output_dir,
+                symbol,
+                bucket_time.format("%Y%m%d_%H%M%S_%3f")
+            );
+            fs::write(&filename, serde_json::to_string_pretty(&output)?)?;
+            written += 1;
+        }
+    }
+
+    println!(
+        "Wrote {} {} snapshots at {}ms to {}",
+        written,
+        args.method.name(),
+        args.interval_ms,
+        args.output_dir
+    );
+    Ok(())
+}
@@ -0,0 +1,155 @@
+// `--retain`: a background task that every SCAN_INTERVAL deletes the oldest
+// snapshot files in the output directory (and its part_NNNNN subdirectories) until
+// the policy holds. Only `orderbook_<symbol>_*` files of the captured symbols are
+// considered, together with their `.meta` sidecars; in-progress `.tmp` files,
+// sequence state, summaries and everything else are left alone.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+
+const SCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    Count(usize),  // Keep the newest N files
+    Age(Duration), // Delete files last modified longer ago than this
+    Size(u64),     // Keep the newest files that fit in this many bytes
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Policy::Count(count) => write!(f, "the newest {} snapshot files", count),
+            Policy::Age(age) => write!(f, "snapshot files up to {}s old", age.as_secs()),
+            Policy::Size(bytes) => write!(f, "the newest {} bytes of snapshot files", bytes),
+        }
+    }
+}
+
+struct Snapshot {
+    path: PathBuf,
+    meta: Option<PathBuf>,
+    modified: SystemTime,
+    bytes: u64, // Including the sidecar
+}
+
+/// Start pruning `output_dir` in the background for as long as the runtime lives.
+pub fn spawn(output_dir: String, symbols: Vec<String>, policy: Policy) {
+    info!(
+        "Keeping {} in {}, checked every {}s",
+        policy,
+        output_dir,
+        SCAN_INTERVAL.as_secs()
+    );
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCAN_INTERVAL);
+        loop {
+            interval.tick().await;
+            let (dir, symbols) = (output_dir.clone(), symbols.clone());
+            match tokio::task::spawn_blocking(move || prune(&dir, &symbols, policy)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!("Retention scan of {} failed: {}", output_dir, e),
+                Err(e) => warn!("Retention scan of {} panicked: {}", output_dir, e),
+            }
+        }
+    });
+}
+
+fn prune(output_dir: &str, symbols: &[String], policy: Policy) -> std::io::Result<()> {
+    if !Path::new(output_dir).is_dir() {
+        return Ok(()); // Nothing saved yet
+    }
+    let mut snapshots = Vec::new();
+    collect(Path::new(output_dir), symbols, &mut snapshots)?;
+    for entry in fs::read_dir(output_dir)? {
+        let path = entry?.path();
+        let is_part = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("part_"));
+        if is_part && path.is_dir() {
+            collect(&path, symbols, &mut snapshots)?;
+        }
+    }
+    // Newest first, so everything past the cut-off is older than what is kept
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.modified));
+
+    let now = SystemTime::now();
+    let mut kept_bytes = 0;
+    let mut deleted: Vec<&Snapshot> = Vec::new();
+    for (index, snapshot) in snapshots.iter().enumerate() {
+        kept_bytes += snapshot.bytes;
+        let expired = match policy {
+            Policy::Count(count) => index >= count,
+            Policy::Age(max_age) => now
+                .duration_since(snapshot.modified)
+                .is_ok_and(|age| age > max_age),
+            Policy::Size(max_bytes) => kept_bytes > max_bytes,
+        };
+        if expired {
+            deleted.push(snapshot);
+        }
+    }
+    if deleted.is_empty() {
+        return Ok(());
+    }
+
+    let mut freed = 0;
+    let mut removed = 0;
+    for snapshot in &deleted {
+        if let Err(e) = fs::remove_file(&snapshot.path) {
+            warn!("Could not delete {}: {}", snapshot.path.display(), e);
+            continue;
+        }
+        if let Some(meta) = &snapshot.meta {
+            let _ = fs::remove_file(meta);
+        }
+        debug!("Deleted {}", snapshot.path.display());
+        freed += snapshot.bytes;
+        removed += 1;
+    }
+    // `deleted` is newest first
+    info!(
+        "Retention deleted {} snapshot files ({} bytes), {} to {}",
+        removed,
+        freed,
+        deleted[deleted.len() - 1].path.display(),
+        deleted[0].path.display()
+    );
+    Ok(())
+}
+
+// Snapshot files of `symbols` directly inside `dir`
+fn collect(dir: &Path, symbols: &[String], snapshots: &mut Vec<Snapshot>) -> std::io::Result<()> {
+    let prefixes: Vec<String> = symbols
+        .iter()
+        .map(|symbol| format!("orderbook_{}_", symbol))
+        .collect();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else { continue };
+        if !prefixes.iter().any(|prefix| name.starts_with(prefix))
+            || name.ends_with(".meta")
+            || name.ends_with(".tmp")
+        {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let meta = dir.join(format!("{}.meta", name));
+        let meta_bytes = fs::metadata(&meta).map(|m| m.len()).ok();
+        snapshots.push(Snapshot {
+            path: entry.path(),
+            meta: meta_bytes.map(|_| meta),
+            modified: metadata.modified()?,
+            bytes: metadata.len() + meta_bytes.unwrap_or_default(),
+        });
+    }
+    Ok(())
+}
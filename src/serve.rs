@@ -0,0 +1,124 @@
+// `--serve-addr`: the latest accepted snapshot of each symbol over HTTP, so other
+// processes can read the current book without racing the file writer.
+//
+//   GET /orderbook?symbol=SUIUSDT   the CombinedData record as saved to disk
+//   GET /price?symbol=SUIUSDT       its current_price plus local_timestamp
+//
+// `symbol` may be left out when only one symbol is captured. Both answer 404 until
+// the symbol's first snapshot; local_timestamp tells readers how stale it is.
+
+use crate::CombinedData;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{routing::get, Json, Router};
+use binance_price_checker::{normalize_symbol, PriceData};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+/// Published by the capture loop, read by the handlers; keyed by symbol.
+pub type Latest = Arc<RwLock<HashMap<String, CombinedData>>>;
+
+#[derive(Deserialize)]
+struct SymbolQuery {
+    symbol: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LatestPrice<'a> {
+    symbol: &'a str,
+    #[serde(flatten)]
+    current_price: &'a PriceData,
+    local_timestamp: u64,
+}
+
+/// Store `data` as `symbol`'s latest snapshot.
+pub fn publish(latest: &Latest, symbol: &str, data: CombinedData) {
+    if let Ok(mut snapshots) = latest.write() {
+        snapshots.insert(symbol.to_string(), data);
+    }
+}
+
+#[derive(Clone)]
+struct ServeState {
+    latest: Latest,
+    symbols: Arc<Vec<String>>, // Captured symbols, to tell "not yet" from "never"
+}
+
+async fn orderbook(State(state): State<ServeState>, Query(query): Query<SymbolQuery>) -> Response {
+    with_snapshot(&state, query, |_, data| Json(data).into_response())
+}
+
+async fn price(State(state): State<ServeState>, Query(query): Query<SymbolQuery>) -> Response {
+    with_snapshot(&state, query, |symbol, data| {
+        Json(LatestPrice {
+            symbol,
+            current_price: &data.current_price,
+            local_timestamp: data.local_timestamp,
+        })
+        .into_response()
+    })
+}
+
+// Run `respond` on the requested symbol's snapshot, or answer with an error
+fn with_snapshot(
+    state: &ServeState,
+    query: SymbolQuery,
+    respond: impl FnOnce(&str, &CombinedData) -> Response,
+) -> Response {
+    let symbols = state.symbols.as_slice();
+    let symbol = match (query.symbol, symbols) {
+        (Some(symbol), _) => match normalize_symbol(&symbol) {
+            Ok(symbol) => symbol,
+            Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+        },
+        (None, [only]) => only.clone(),
+        (None, _) => {
+            let message = format!("Pass ?symbol= (one of {})", symbols.join(", "));
+            return (StatusCode::BAD_REQUEST, message).into_response();
+        }
+    };
+    let Ok(snapshots) = state.latest.read() else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    match snapshots.get(&symbol) {
+        Some(data) => respond(&symbol, data),
+        None if symbols.contains(&symbol) => {
+            (StatusCode::NOT_FOUND, format!("No {} snapshot yet", symbol)).into_response()
+        }
+        None => (StatusCode::NOT_FOUND, format!("{} is not captured", symbol)).into_response(),
+    }
+}
+
+/// Bind `addr` and serve the endpoints until `shutdown` fires (or its sender is dropped).
+pub async fn serve(
+    addr: SocketAddr,
+    latest: Latest,
+    symbols: Vec<String>,
+    shutdown: oneshot::Receiver<()>,
+) -> std::io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving the latest snapshots on http://{}/orderbook", addr);
+    let state = ServeState {
+        latest,
+        symbols: Arc::new(symbols),
+    };
+    let app = Router::new()
+        .route("/orderbook", get(orderbook))
+        .route("/price", get(price))
+        .with_state(state);
+    Ok(tokio::spawn(async move {
+        let server = axum::serve(listener, app).with_graceful_shutdown(async {
+            let _ = shutdown.await;
+        });
+        if let Err(e) = server.await {
+            error!("Snapshot server failed: {}", e);
+        }
+    }))
+}
@@ -0,0 +1,193 @@
+use binance_price_checker::ansi::palette;
+use binance_price_checker::logging;
+use binance_price_checker::{http_client_from_args, BinanceClient, Level, OrderBook};
+use std::error::Error;
+use std::sync::Arc;
+use tokio::join;
+use tokio::time::Duration;
+use tracing::info;
+
+use binance_price_checker::{keyboard, table};
+
+const BASE_URL: &str = "https://api.binance.com";
+const USDT_SYMBOL: &str = "SUIUSDT";
+const USDC_SYMBOL: &str = "SUIUSDC";
+const PARITY_SYMBOL: &str = "USDCUSDT"; // USDT per USDC
+const LEVELS: usize = 10;
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+const COLUMN_WIDTH: usize = 22;
+const HUMAN_QTY: bool = false; // Show quantities as 1.52M instead of 1523000.00
+
+async fn fetch_orderbook(api: &BinanceClient, symbol: &str) -> Result<OrderBook, Box<dyn Error>> {
+    let (orderbook, _) = api.get_orderbook_snapshot(symbol, LEVELS as u32).await?;
+    Ok(orderbook)
+}
+
+async fn fetch_price(api: &BinanceClient, symbol: &str) -> Result<f64, Box<dyn Error>> {
+    let ticker = api.get_current_price(symbol).await?;
+    Ok(ticker.price.parse::<f64>()?)
+}
+
+fn best_price(levels: &[Level]) -> Option<f64> {
+    levels.first().map(Level::price_f64)
+}
+
+// Render one side-by-side cell, or a placeholder when the book is missing or too shallow
+fn cell(book: &Option<OrderBook>, level: usize, is_bid: bool) -> String {
+    let side = book
+        .as_ref()
+        .map(|b| if is_bid { &b.bids } else { &b.asks });
+    match side.and_then(|levels| levels.get(level)) {
+        Some(entry) => {
+            if HUMAN_QTY {
+                format!(
+                    "{:>10.4}$ {:>10}",
+                    entry.price,
+                    table::human_qty(entry.qty_f64())
+                )
+            } else {
+                format!("{:>10.4}$ {:>10.2}", entry.price, entry.qty)
+            }
+        }
+        None => format!("{:>width$}", "-", width = COLUMN_WIDTH),
+    }
+}
+
+fn print_cross_spread(usdt: &OrderBook, usdc: &OrderBook, parity: f64) {
+    let (Some(usdt_bid), Some(usdt_ask), Some(usdc_bid), Some(usdc_ask)) = (
+        best_price(&usdt.bids),
+        best_price(&usdt.asks),
+        best_price(&usdc.bids),
+        best_price(&usdc.asks),
+    ) else {
+        println!("Cross-quote spread unavailable (empty book side)");
+        return;
+    };
+
+    // Express the USDC book in USDT so the two are directly comparable
+    let usdc_bid_in_usdt = usdc_bid * parity;
+    let usdc_ask_in_usdt = usdc_ask * parity;
+    let mid = (usdt_bid + usdt_ask) / 2.0;
+
+    let edges = [
+        (
+            format!("Buy {} / sell {}", USDC_SYMBOL, USDT_SYMBOL),
+            usdt_bid - usdc_ask_in_usdt,
+        ),
+        (
+            format!("Buy {} / sell {}", USDT_SYMBOL, USDC_SYMBOL),
+            usdc_bid_in_usdt - usdt_ask,
+        ),
+    ];
+
+    let ansi = palette();
+    println!("Cross-quote spread (USDC converted at {:.6} USDT):", parity);
+    for (label, edge) in edges {
+        let color = if edge > 0.0 { ansi.green } else { ansi.red };
+        println!(
+            "  {:<28} {}{:+.6}$ ({:+.2} bps){}",
+            label,
+            color,
+            edge,
+            edge / mid * 10_000.0,
+            ansi.reset
+        );
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    logging::init(logging::format_from_args()?, false);
+    let ansi = palette();
+    let api = BinanceClient::new(http_client_from_args()?, BASE_URL);
+    let controls = Arc::new(keyboard::Controls::default());
+    keyboard::watch_ctrl_c(controls.clone());
+    let mut refreshes: u64 = 0;
+
+    while !controls.is_shutting_down() {
+        refreshes += 1;
+        // Fetch both books and the stablecoin parity in parallel
+        let (usdt_result, usdc_result, parity_result) = join!(
+            fetch_orderbook(&api, USDT_SYMBOL),
+            fetch_orderbook(&api, USDC_SYMBOL),
+            fetch_price(&api, PARITY_SYMBOL)
+        );
+
+        // Clear screen
+        print!("{}", ansi.clear);
+
+        let usdt_book = match usdt_result {
+            Ok(book) => Some(book),
+            Err(e) => {
+                println!(
+                    "{}{} unavailable: {}{}",
+                    ansi.yellow, USDT_SYMBOL, e, ansi.reset
+                );
+                None
+            }
+        };
+        let usdc_book = match usdc_result {
+            Ok(book) => Some(book),
+            Err(e) => {
+                println!(
+                    "{}{} unavailable: {}{}",
+                    ansi.yellow, USDC_SYMBOL, e, ansi.reset
+                );
+                None
+            }
+        };
+        let parity = match parity_result {
+            Ok(price) => price,
+            Err(e) => {
+                println!(
+                    "{}{} unavailable ({}), assuming 1:1 parity{}",
+                    ansi.yellow, PARITY_SYMBOL, e, ansi.reset
+                );
+                1.0
+            }
+        };
+
+        println!(
+            "{:>width$} | {:>width$}",
+            USDT_SYMBOL,
+            USDC_SYMBOL,
+            width = COLUMN_WIDTH
+        );
+
+        // Asks from worst to best so both books meet at the spread
+        for level in (0..LEVELS).rev() {
+            println!(
+                "{}{} | {}{}",
+                ansi.red,
+                cell(&usdt_book, level, false),
+                cell(&usdc_book, level, false),
+                ansi.reset
+            );
+        }
+
+        println!();
+
+        for level in 0..LEVELS {
+            println!(
+                "{}{} | {}{}",
+                ansi.green,
+                cell(&usdt_book, level, true),
+                cell(&usdc_book, level, true),
+                ansi.reset
+            );
+        }
+
+        println!();
+
+        match (&usdt_book, &usdc_book) {
+            (Some(usdt), Some(usdc)) => print_cross_spread(usdt, usdc, parity),
+            _ => println!("Cross-quote spread unavailable (one quote is missing)"),
+        }
+
+        // Returns early on Ctrl+C
+        controls.wait(REFRESH_INTERVAL).await;
+    }
+
+    info!("Stopped after {} refreshes", refreshes);
+    Ok(())
+}
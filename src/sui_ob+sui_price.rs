@@ -1,64 +1,48 @@
-use reqwest;
-use serde::{Deserialize, Serialize};
-use std::thread;
-use std::{error::Error, str::FromStr};
-use tokio::time::{sleep, Duration};
-
-// ANSI color codes
-const RED: &str = "\x1b[31m";
-const GREEN: &str = "\x1b[32m";
-const RESET: &str = "\x1b[0m";
-
-#[derive(Serialize, Deserialize, Debug)]
-struct TickerPrice {
-    symbol: String,
-    price: String,
-}
+use binance_price_checker::ansi::palette;
+use binance_price_checker::logging;
+use binance_price_checker::{http_client_from_args, BinanceClient, Level};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::error::Error;
+use std::sync::Arc;
+use tracing::{error, info};
 
-#[derive(Serialize, Deserialize, Debug)]
-struct OrderBook {
-    lastUpdateId: u64,
-    bids: Vec<[String; 2]>,
-    asks: Vec<[String; 2]>,
-}
+use binance_price_checker::{keyboard, table};
+
+const BASE_URL: &str = "https://api.binance.com";
+
+// Measure column widths from the visible levels instead of fixed `{:>8}` padding
+const ALIGN_COLUMNS: bool = true;
+
+// Show quantities as 1.52M instead of 1523000.0000 (display only)
+const HUMAN_QTY: bool = false;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    logging::init(logging::format_from_args()?, false);
+    let ansi = palette();
     let symbol = "SUIUSDT";
-    let ticker_url = format!(
-        "https://api.binance.com/api/v3/ticker/price?symbol={}",
-        symbol
-    );
     let depth_limit = 10;
-    let depth_url = format!(
-        "https://api.binance.com/api/v3/depth?symbol={}&limit={}",
-        symbol, depth_limit
-    );
+    let api = BinanceClient::new(http_client_from_args()?, BASE_URL);
 
     let mut previous_price: Option<f64> = None;
+    let controls = Arc::new(keyboard::Controls::default());
+    keyboard::watch_ctrl_c(controls.clone());
+    let mut refreshes: u64 = 0;
 
-    loop {
+    while !controls.is_shutting_down() {
+        refreshes += 1;
         // Fetch ticker price
-        let current_price = match reqwest::get(&ticker_url).await {
-            Ok(resp) if resp.status().is_success() => match resp.json::<TickerPrice>().await {
-                Ok(ticker) => match ticker.price.parse::<f64>() {
-                    Ok(price) => Some((ticker.symbol, price)),
-                    Err(e) => {
-                        eprintln!("Error parsing ticker price: {}", e);
-                        None
-                    }
-                },
+        let current_price = match api.get_current_price(symbol).await {
+            Ok(ticker) => match ticker.price.parse::<f64>() {
+                Ok(price) => Some((symbol, price)),
                 Err(e) => {
-                    eprintln!("Error parsing ticker JSON: {}", e);
+                    error!("Error parsing ticker price: {}", e);
                     None
                 }
             },
-            Ok(resp) => {
-                eprintln!("Ticker HTTP error: {}", resp.status());
-                None
-            }
             Err(e) => {
-                eprintln!("Ticker request error: {}", e);
+                error!("Ticker error: {}", e);
                 None
             }
         };
@@ -70,15 +54,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 let change = price - prev;
                 let change_percent = (change / prev) * 100.0;
                 let color = if price > prev {
-                    GREEN
+                    ansi.green
                 } else if price < prev {
-                    RED
+                    ansi.red
                 } else {
-                    RESET
+                    ansi.reset
                 };
                 println!(
                     "[{}] {}: {}{:.6}$ ( {:+.6}$, {:+.2}% ){}",
-                    timestamp, sym, color, price, change, change_percent, RESET
+                    timestamp, sym, color, price, change, change_percent, ansi.reset
                 );
             } else {
                 println!("[{}] {}: ${:.6}", timestamp, sym, price);
@@ -91,27 +75,48 @@ async fn main() -> Result<(), Box<dyn Error>> {
         println!(); // Blank line before order book
 
         // Fetch order book snapshot
-        match reqwest::get(&depth_url).await {
-            Ok(response) => {
-                if !response.status().is_success() {
-                    eprintln!("HTTP Error: {}", response.status());
-                } else if let Ok(orderbook) = response.json::<OrderBook>().await {
-                    println!("{} Orderbook for {} {} ", RESET, symbol, RESET);
-
-                    // Helper closure to parse and format floats with 4 decimals
-                    let fmt_4dec = |s: &String| -> String {
-                        match s.parse::<f64>() {
-                            Ok(val) => format!("{:.4}", val),
-                            Err(_) => s.clone(),
-                        }
+        match api.get_orderbook_snapshot(symbol, depth_limit).await {
+            Ok((orderbook, _)) => {
+                println!("{} Orderbook for {} {} ", ansi.reset, symbol, ansi.reset);
+
+                // Helper closure to format decimals with 4 places
+                let fmt_4dec = |value: &Decimal| -> String { format!("{:.4}", value) };
+                let fmt_qty = |value: &Decimal| -> String {
+                    match value.to_f64() {
+                        Some(val) if HUMAN_QTY => table::human_qty(val),
+                        _ => fmt_4dec(value),
+                    }
+                };
+
+                if ALIGN_COLUMNS {
+                    let format_levels = |levels: &[Level]| -> Vec<Vec<String>> {
+                        levels
+                            .iter()
+                            .map(|level| {
+                                vec![format!("{}$", fmt_4dec(&level.price)), fmt_qty(&level.qty)]
+                            })
+                            .collect()
                     };
+                    let mut rows = format_levels(&orderbook.bids);
+                    rows.extend(format_levels(&orderbook.asks));
 
+                    // Measure both sides together so bids and asks share column widths
+                    let lines = table::render_rows(&rows, "  ");
+                    let (bid_lines, ask_lines) = lines.split_at(orderbook.bids.len());
+                    for line in bid_lines {
+                        println!("{}  {}{}", ansi.green, line, ansi.reset);
+                    }
+                    println!();
+                    for line in ask_lines {
+                        println!("{}  {}{}", ansi.red, line, ansi.reset);
+                    }
+                } else {
                     // Print Bids
                     for bid in &orderbook.bids {
-                        let price = fmt_4dec(&bid[0]);
-                        let qty = fmt_4dec(&bid[1]);
+                        let price = fmt_4dec(&bid.price);
+                        let qty = fmt_qty(&bid.qty);
                         // Print in green
-                        println!("{}  {:>8}$  {:>8}{}", GREEN, price, qty, RESET);
+                        println!("{}  {:>8}$  {:>8}{}", ansi.green, price, qty, ansi.reset);
                     }
 
                     // Blank line
@@ -119,20 +124,21 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
                     // Print Asks
                     for ask in &orderbook.asks {
-                        let price = fmt_4dec(&ask[0]);
-                        let qty = fmt_4dec(&ask[1]);
+                        let price = fmt_4dec(&ask.price);
+                        let qty = fmt_qty(&ask.qty);
                         // Print in red
-                        println!("{}  {:>8}$ {:>8}{}", RED, price, qty, RESET);
+                        println!("{}  {:>8}$ {:>8}{}", ansi.red, price, qty, ansi.reset);
                     }
-                } else {
-                    eprintln!("Error parsing JSON response.");
                 }
             }
             Err(e) => {
-                eprintln!("Request error: {}", e);
+                error!("Order book error: {}", e);
             }
         }
         // Clear the console (ANSI escape codes)
-        print!("\x1b[2J\x1b[H");
+        print!("{}", ansi.clear);
     }
+
+    info!("Stopped after {} refreshes", refreshes);
+    Ok(())
 }
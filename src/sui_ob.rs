@@ -1,73 +1,666 @@
-use reqwest;
-use serde::{Deserialize, Serialize};
-use std::{error::Error, str::FromStr};
-use tokio::time::{sleep, Duration};
+use binance_price_checker::ansi::palette;
+use binance_price_checker::logging;
+use binance_price_checker::{
+    depth_imbalance, http_client_from_args, mid_price, normalize_symbol, vwap_for_quantity,
+    weight_limit, with_cumulative, BinanceClient, CumulativeLevel, Level, OrderBook, PriceData,
+    Side, SymbolFilters,
+};
+use flate2::read::GzDecoder;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::time::Duration;
+use tracing::{error, info, warn};
 
-const RED: &str = "\x1b[31m";
-const GREEN: &str = "\x1b[32m";
-const RESET: &str = "\x1b[0m";
+use binance_price_checker::endpoint::{self, Endpoint};
+use binance_price_checker::{keyboard, table};
 
-#[derive(Serialize, Deserialize, Debug)]
-struct OrderBook {
-    lastUpdateId: u64,
-    bids: Vec<[String; 2]>,
-    asks: Vec<[String; 2]>,
+// Measure column widths from the visible levels instead of fixed `{:>8}` padding
+const ALIGN_COLUMNS: bool = true;
+
+const SYMBOL: &str = "SUIUSDT"; // Default for --symbol
+
+// Used when exchangeInfo can't be fetched; Binance's long-standing per-minute weight limit
+const DEFAULT_WEIGHT_LIMIT: u32 = 1200;
+const GAUGE_WIDTH: usize = 10;
+const WEIGHT_THROTTLE_FRACTION: f64 = 0.9; // Hold off until the next minute past this share
+
+// Levels per side summed for the bid/ask imbalance bar above the book
+const IMBALANCE_LEVELS: usize = 10;
+const IMBALANCE_WIDTH: usize = 20;
+
+// Shown when neither exchangeInfo nor --price-decimals/--qty-decimals give a precision
+const DEFAULT_DECIMALS: usize = 4;
+
+// Level brightness by distance from mid: bold within the first, plain within the
+// second, dim beyond (percent of mid)
+const NEAR_PCT: Decimal = Decimal::from_parts(1, 0, 0, false, 1); // 0.1%
+const MID_PCT: Decimal = Decimal::from_parts(5, 0, 0, false, 1); // 0.5%
+
+const REPLAY_USAGE: &str = "Usage: sui_ob replay DIR [--speed realtime|10x] [--vwap-qty QTY] \
+                            [--price-decimals N] [--qty-decimals N] [--human-qty] [--cumulative] \
+                            [--diff-against-mid | --reference PRICE] [--no-color]";
+
+// Redraw from the top; without color each refresh is appended after a blank line
+fn clear_screen() {
+    let ansi = palette();
+    if ansi.is_plain() {
+        println!();
+    } else {
+        print!("{}", ansi.clear);
+    }
+}
+
+// Decimal places prices and quantities are shown with
+#[derive(Debug, Clone, Copy)]
+struct Precision {
+    price: usize,
+    qty: usize,
+}
+
+// Optional columns and quantity format, from the command line
+#[derive(Debug, Clone, Copy)]
+struct View {
+    // Quantities as 1.52M instead of 1523000.0000, overriding --qty-decimals (--human-qty)
+    human_qty: bool,
+    // After each level's Σ quantity from the top of its side, also Σ price × qty in the
+    // quote asset (--cumulative)
+    cumulative_notional: bool,
+    // Extra column with each level's distance, in $ and bps, from mid
+    // (--diff-against-mid) or from a fixed price (--reference PRICE)
+    diff_against_mid: bool,
+    reference_price: Option<f64>,
+}
+
+// The parts of a main.rs snapshot file the replay shows; other fields are ignored
+#[derive(Deserialize)]
+struct SavedSnapshot {
+    #[serde(flatten)]
+    orderbook: OrderBook,
+    local_datetime: String,
+}
+
+// local_timestamp only has whole seconds; the ticker receive time (ms) spaces the replay
+#[derive(Deserialize)]
+struct SavedTimestamp {
+    current_price: PriceData,
+}
+
+// e.g. [████████████░░░░░░░░] 60% bids, bid share in green and ask share in red
+// (░ for the ask share when there is no color to tell them apart)
+fn imbalance_bar(bid_share: f64) -> String {
+    let bid_cells = ((bid_share * IMBALANCE_WIDTH as f64).round() as usize).min(IMBALANCE_WIDTH);
+    let ansi = palette();
+    let ask_cell = if ansi.is_plain() { "░" } else { "█" };
+    format!(
+        "[{}{}{}{}{}] {:.0}% bids",
+        ansi.green,
+        "█".repeat(bid_cells),
+        ansi.red,
+        ask_cell.repeat(IMBALANCE_WIDTH - bid_cells),
+        ansi.reset,
+        bid_share * 100.0
+    )
+}
+
+// `--symbol SYMBOL`: the pair to watch, SYMBOL without it
+fn symbol_arg() -> Result<String, String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--symbol" {
+            return normalize_symbol(&args.next().ok_or("Usage: --symbol SYMBOL")?);
+        }
+    }
+    Ok(SYMBOL.to_string())
+}
+
+// `--vwap-qty QTY`: also show the average price of buying and selling QTY
+fn vwap_qty_arg() -> Result<Option<Decimal>, String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--vwap-qty" {
+            let value = args.next().ok_or("Usage: --vwap-qty QTY")?;
+            return match value.parse::<Decimal>() {
+                Ok(qty) if qty > Decimal::ZERO => Ok(Some(qty)),
+                _ => Err(format!("--vwap-qty '{}' is not a positive quantity", value)),
+            };
+        }
+    }
+    Ok(None)
+}
+
+// Whether the switch `flag` was passed
+fn flag_arg(flag: &str) -> bool {
+    std::env::args().skip(1).any(|arg| arg == flag)
+}
+
+fn view_args() -> Result<View, String> {
+    Ok(View {
+        human_qty: flag_arg("--human-qty"),
+        cumulative_notional: flag_arg("--cumulative"),
+        diff_against_mid: flag_arg("--diff-against-mid"),
+        reference_price: reference_arg()?,
+    })
+}
+
+// `--reference PRICE`: show each level's distance from PRICE
+fn reference_arg() -> Result<Option<f64>, String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--reference" {
+            let value = args.next().ok_or("Usage: --reference PRICE")?;
+            return match value.parse::<f64>() {
+                Ok(price) if price > 0.0 && price.is_finite() => Ok(Some(price)),
+                _ => Err(format!("--reference '{}' is not a positive price", value)),
+            };
+        }
+    }
+    Ok(None)
+}
+
+// `--price-decimals N` / `--qty-decimals N`: used instead of the exchangeInfo precision
+fn decimals_arg(flag: &str) -> Result<Option<usize>, String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            let value = args.next().ok_or(format!("Usage: {} N", flag))?;
+            return value
+                .parse::<usize>()
+                .map(Some)
+                .map_err(|_| format!("{} '{}' is not a number of decimals", flag, value));
+        }
+    }
+    Ok(None)
+}
+
+// The flags where given, then exchangeInfo's filters, then DEFAULT_DECIMALS
+fn precision(filters: Option<&SymbolFilters>) -> Result<Precision, String> {
+    Ok(Precision {
+        price: decimals_arg("--price-decimals")?
+            .or(filters.map(SymbolFilters::price_decimals))
+            .unwrap_or(DEFAULT_DECIMALS),
+        qty: decimals_arg("--qty-decimals")?
+            .or(filters.map(SymbolFilters::qty_decimals))
+            .unwrap_or(DEFAULT_DECIMALS),
+    })
+}
+
+// e.g. [████░░░░░░] 420/1200, green under 50%, yellow under 80%, red above
+fn weight_gauge(used: u32, limit: u32) -> String {
+    let ratio = used as f64 / limit.max(1) as f64;
+    let filled = ((ratio * GAUGE_WIDTH as f64).round() as usize).min(GAUGE_WIDTH);
+    let ansi = palette();
+    let color = if ratio < 0.5 {
+        ansi.green
+    } else if ratio < 0.8 {
+        ansi.yellow
+    } else {
+        ansi.red
+    };
+    format!(
+        "{}[{}{}] {}/{}{}",
+        color,
+        "█".repeat(filled),
+        "░".repeat(GAUGE_WIDTH - filled),
+        used,
+        limit,
+        ansi.reset
+    )
+}
+
+// Where the best bid sits against the best ask; anything but Normal means the book
+// can't be trusted (usually stale)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MarketState {
+    Normal,
+    Locked,  // Best bid == best ask
+    Crossed, // Best bid > best ask
+    Unknown, // A side is empty
+}
+
+fn market_state(book: &OrderBook) -> MarketState {
+    match (book.bids.first(), book.asks.first()) {
+        (Some(bid), Some(ask)) => match bid.price.cmp(&ask.price) {
+            Ordering::Less => MarketState::Normal,
+            Ordering::Equal => MarketState::Locked,
+            Ordering::Greater => MarketState::Crossed,
+        },
+        _ => MarketState::Unknown,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DistanceBucket {
+    Near, // Under NEAR_PCT from mid
+    Mid,  // Under MID_PCT
+    Far,
+}
+
+impl DistanceBucket {
+    // Prefix for the side's color
+    fn intensity(self) -> &'static str {
+        match self {
+            DistanceBucket::Near => palette().bold,
+            DistanceBucket::Mid => "",
+            DistanceBucket::Far => palette().dim,
+        }
+    }
+}
+
+fn level_bucket(price: Decimal, mid: Decimal) -> DistanceBucket {
+    if mid <= Decimal::ZERO {
+        return DistanceBucket::Far;
+    }
+    let pct = (price - mid).abs() / mid * Decimal::ONE_HUNDRED;
+    if pct < NEAR_PCT {
+        DistanceBucket::Near
+    } else if pct < MID_PCT {
+        DistanceBucket::Mid
+    } else {
+        DistanceBucket::Far
+    }
+}
+
+// The book with the imbalance bar, colored levels and the --vwap-qty line
+fn render_book(
+    orderbook: &OrderBook,
+    symbol: &str,
+    vwap_qty: Option<Decimal>,
+    precision: Precision,
+    view: View,
+) {
+    let ansi = palette();
+    println!("{} Orderbook for {} {} ", ansi.reset, symbol, ansi.reset);
+    let state = market_state(orderbook);
+    if matches!(state, MarketState::Locked | MarketState::Crossed) {
+        let (bid, ask) = (&orderbook.bids[0].price, &orderbook.asks[0].price);
+        println!(
+            "{}{}!! {:?} market: best bid {} vs best ask {}, data may be stale !!{}",
+            ansi.bold, ansi.red, state, bid, ask, ansi.reset
+        );
+    }
+    // Side color plus brightness by distance from mid; no mid, no brightness
+    let mid = mid_price(orderbook);
+    let color = |side_color: &'static str, price: Decimal| -> String {
+        let intensity = mid.map_or("", |mid| level_bucket(price, mid).intensity());
+        format!("{}{}", intensity, side_color)
+    };
+    println!(
+        "Imbalance (top {}) {}",
+        IMBALANCE_LEVELS,
+        imbalance_bar(depth_imbalance(orderbook, IMBALANCE_LEVELS))
+    );
+
+    // Prices to the tick size's decimals, quantities to the step size's
+    let fmt_price = |value: &Decimal| -> String { format!("{:.*}", precision.price, value) };
+    let fmt_qty = |value: &Decimal| -> String {
+        match value.to_f64() {
+            Some(val) if view.human_qty => table::human_qty(val),
+            _ => format!("{:.*}", precision.qty, value),
+        }
+    };
+
+    let best = |levels: &[Level]| levels.first().map(Level::price_f64);
+    let reference = match (view.reference_price, view.diff_against_mid) {
+        (Some(price), _) => Some(price),
+        (None, false) => None,
+        (None, true) => match (best(&orderbook.bids), best(&orderbook.asks)) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+            _ => None,
+        },
+    };
+    // Negative below the reference, positive above
+    let distance = |price: &Decimal| -> Option<[String; 2]> {
+        let reference = reference?;
+        let diff = price.to_f64()? - reference;
+        Some([
+            format!("{:+.*}$", precision.price, diff),
+            format!("{:+.1}bps", diff / reference * 10_000.0),
+        ])
+    };
+    if let Some(reference) = reference {
+        println!("Distance from {:.*}$", precision.price, reference);
+    }
+    let cumulative = |level: &CumulativeLevel| -> Vec<String> {
+        let mut cells = vec![format!("Σ{}", fmt_qty(&level.cum_qty))];
+        if view.cumulative_notional {
+            cells.push(format!("Σ{:.2}$", level.cum_notional));
+        }
+        cells
+    };
+    let bids = with_cumulative(orderbook, Side::Bid);
+    let asks = with_cumulative(orderbook, Side::Ask);
+
+    if ALIGN_COLUMNS {
+        let format_levels = |levels: &[CumulativeLevel]| -> Vec<Vec<String>> {
+            levels
+                .iter()
+                .map(|cumulative_level| {
+                    let level = &cumulative_level.level;
+                    let mut row =
+                        vec![format!("{}$", fmt_price(&level.price)), fmt_qty(&level.qty)];
+                    row.extend(cumulative(cumulative_level));
+                    row.extend(distance(&level.price).into_iter().flatten());
+                    row
+                })
+                .collect()
+        };
+        let mut rows = format_levels(&bids);
+        rows.extend(format_levels(&asks));
+
+        // Measure both sides together so bids and asks share column widths
+        let lines = table::render_rows(&rows, "  ");
+        let (bid_lines, ask_lines) = lines.split_at(orderbook.bids.len());
+        for (line, bid) in bid_lines.iter().zip(&orderbook.bids) {
+            println!("{}  {}{}", color(ansi.green, bid.price), line, ansi.reset);
+        }
+        println!();
+        for (line, ask) in ask_lines.iter().zip(&orderbook.asks) {
+            println!("{}  {}{}", color(ansi.red, ask.price), line, ansi.reset);
+        }
+    } else {
+        // Print Bids
+        for cumulative_bid in &bids {
+            let bid = &cumulative_bid.level;
+            let price = fmt_price(&bid.price);
+            let qty = fmt_qty(&bid.qty);
+            let cum = cumulative(cumulative_bid).join(" ");
+            // Print in green
+            let diff = distance(&bid.price)
+                .map(|d| d.join(" "))
+                .unwrap_or_default();
+            println!(
+                "{}  {:>8}$  {:>8}  {:>10}  {}{}",
+                color(ansi.green, bid.price),
+                price,
+                qty,
+                cum,
+                diff,
+                ansi.reset
+            );
+        }
+
+        // Blank line
+        println!();
+
+        // Print Asks
+        for cumulative_ask in &asks {
+            let ask = &cumulative_ask.level;
+            let price = fmt_price(&ask.price);
+            let qty = fmt_qty(&ask.qty);
+            let cum = cumulative(cumulative_ask).join(" ");
+            // Print in red
+            let diff = distance(&ask.price)
+                .map(|d| d.join(" "))
+                .unwrap_or_default();
+            println!(
+                "{}  {:>8}$ {:>8}  {:>10}  {}{}",
+                color(ansi.red, ask.price),
+                price,
+                qty,
+                cum,
+                diff,
+                ansi.reset
+            );
+        }
+    }
+
+    if let Some(qty) = vwap_qty {
+        println!();
+        for (label, side, color) in [
+            ("Sell", Side::Bid, ansi.green),
+            ("Buy", Side::Ask, ansi.red),
+        ] {
+            match vwap_for_quantity(orderbook, side, qty) {
+                Some(vwap) if vwap.complete => println!(
+                    "{}  {} {}: avg {}${}",
+                    color,
+                    label,
+                    qty,
+                    fmt_price(&vwap.avg_price),
+                    ansi.reset
+                ),
+                Some(vwap) => println!(
+                    "{}  {} {}: only {} in view, avg {}${}",
+                    color,
+                    label,
+                    qty,
+                    vwap.filled_qty,
+                    fmt_price(&vwap.avg_price),
+                    ansi.reset
+                ),
+                None => println!("  {} {}: no levels", label, qty),
+            }
+        }
+    }
+}
+
+// `realtime` or a playback factor such as `10x` or `0.5x`
+fn parse_speed(value: &str) -> Result<f64, String> {
+    if value == "realtime" {
+        return Ok(1.0);
+    }
+    match value.strip_suffix('x').unwrap_or(value).parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(format!(
+            "--speed '{}' is not realtime or a factor like 10x",
+            value
+        )),
+    }
+}
+
+// JSON snapshot, gzipped when written with --compress
+fn read_snapshot<T: DeserializeOwned>(path: &Path) -> Result<T, Box<dyn Error>> {
+    let file = File::open(path)?;
+    Ok(if path.extension().is_some_and(|ext| ext == "gz") {
+        serde_json::from_reader(BufReader::new(GzDecoder::new(file)))?
+    } else {
+        serde_json::from_reader(BufReader::new(file))?
+    })
+}
+
+// `orderbook_*.json[.gz]` files in `dir` and its part_NNNNN subdirectories, oldest
+// first by capture time. Files that don't parse are skipped with a warning.
+fn snapshot_files(dir: &Path) -> Result<Vec<(u64, PathBuf)>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            for entry in fs::read_dir(&path)? {
+                paths.push(entry?.path());
+            }
+        } else {
+            paths.push(path);
+        }
+    }
+
+    let mut files = Vec::new();
+    for path in paths {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        if !name.starts_with("orderbook_")
+            || !(name.ends_with(".json") || name.ends_with(".json.gz"))
+        {
+            continue;
+        }
+        match read_snapshot::<SavedTimestamp>(&path) {
+            Ok(saved) => files.push((saved.current_price.timestamp, path)),
+            Err(e) => warn!("Skipping {}: {}", path.display(), e),
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// `sui_ob replay DIR`: show saved snapshots the way the live view does, spaced by the
+// time between their captures divided by --speed
+async fn replay(
+    vwap_qty: Option<Decimal>,
+    precision: Precision,
+    view: View,
+) -> Result<(), Box<dyn Error>> {
+    let mut dir = None;
+    let mut speed = 1.0;
+    let mut args = std::env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--speed" => speed = parse_speed(&args.next().ok_or(REPLAY_USAGE)?)?,
+            // Read by vwap_qty_arg, decimals_arg, reference_arg and logging::format_from_args
+            "--vwap-qty" | "--log-format" | "--price-decimals" | "--qty-decimals"
+            | "--reference" => {
+                args.next();
+            }
+            // Switches read by ansi::palette and view_args
+            "--no-color" | "--human-qty" | "--cumulative" | "--diff-against-mid" => {}
+            "-h" | "--help" => return Err(REPLAY_USAGE.into()),
+            _ if dir.is_none() => dir = Some(arg),
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, REPLAY_USAGE).into()),
+        }
+    }
+    let dir = dir.ok_or(REPLAY_USAGE)?;
+
+    let files = snapshot_files(Path::new(&dir))?;
+    if files.is_empty() {
+        return Err(format!("No orderbook_*.json snapshots in {}", dir).into());
+    }
+
+    let controls = Arc::new(keyboard::Controls::default());
+    keyboard::watch_ctrl_c(controls.clone());
+    let mut shown: u64 = 0;
+    let mut previous: Option<u64> = None;
+
+    for (position, (timestamp, path)) in files.iter().enumerate() {
+        if let Some(previous) = previous {
+            let gap = timestamp.saturating_sub(previous) as f64 / 1000.0 / speed;
+            controls.wait(Duration::from_secs_f64(gap)).await;
+        }
+        if controls.is_shutting_down() {
+            break;
+        }
+        previous = Some(*timestamp);
+
+        let snapshot: SavedSnapshot = match read_snapshot(path) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                warn!("Skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        // orderbook_<SYMBOL>_<YYYYmmdd>_<HHMMSS>...
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let symbol = name
+            .trim_start_matches("orderbook_")
+            .split('_')
+            .next()
+            .unwrap_or_default();
+
+        clear_screen();
+        println!(
+            "Replay {}/{} {} ({})",
+            position + 1,
+            files.len(),
+            snapshot.local_datetime,
+            name
+        );
+        render_book(&snapshot.orderbook, symbol, vwap_qty, precision, view);
+        shown += 1;
+    }
+
+    info!(
+        "Stopped after replaying {} of {} snapshots",
+        shown,
+        files.len()
+    );
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let symbol = "SUIUSDT";
+    let symbol = &symbol_arg()?;
     let limit = 10;
+    logging::init(logging::format_from_args()?, false);
+    let vwap_qty = vwap_qty_arg()?;
+    let view = view_args()?;
+    if std::env::args().nth(1).as_deref() == Some("replay") {
+        // Offline, so only the flags or DEFAULT_DECIMALS
+        return replay(vwap_qty, precision(None)?, view).await;
+    }
+    let endpoint = endpoint::from_args(Endpoint::Com)?;
+    let api = BinanceClient::new(http_client_from_args()?, endpoint.url());
+    // Fetched once at startup for the weight limit and the symbol's filters
+    let exchange_info = match api.get_exchange_info(Some(symbol)).await {
+        Ok(info) => Some(info),
+        Err(e) => {
+            warn!("Could not fetch exchangeInfo: {}", e);
+            None
+        }
+    };
+    let weight_limit = match exchange_info.as_ref().map(weight_limit) {
+        Some(Ok(weight_limit)) => weight_limit,
+        Some(Err(e)) => {
+            warn!("Using default weight limit {}: {}", DEFAULT_WEIGHT_LIMIT, e);
+            DEFAULT_WEIGHT_LIMIT
+        }
+        None => DEFAULT_WEIGHT_LIMIT,
+    };
+    let filters = match exchange_info.map(|info| SymbolFilters::parse(&info, symbol)) {
+        Some(Ok(filters)) => Some(filters),
+        Some(Err(e)) => {
+            warn!("No tick/step size for {}: {}", symbol, e);
+            None
+        }
+        None => None,
+    };
+    let precision = precision(filters.as_ref())?;
 
-    loop {
-        // Clear screen
-        print!("\x1b[2J\x1b[H");
+    let controls = Arc::new(keyboard::Controls::default());
+    keyboard::watch_ctrl_c(controls.clone());
+    let mut refreshes: u64 = 0;
 
-        let url = format!(
-            "https://api.binance.com/api/v3/depth?symbol={}&limit={}",
-            symbol, limit
-        );
+    while !controls.is_shutting_down() {
+        refreshes += 1;
+        clear_screen();
 
-        match reqwest::get(&url).await {
-            Ok(response) => {
-                if !response.status().is_success() {
-                    eprintln!("HTTP Error: {}", response.status());
-                } else if let Ok(orderbook) = response.json::<OrderBook>().await {
-                    println!("{} Orderbook for {} {} ", RESET, symbol, RESET);
-
-                    // Helper closure to parse and format floats with 4 decimals
-                    let fmt_4dec = |s: &String| -> String {
-                        match s.parse::<f64>() {
-                            Ok(val) => format!("{:.4}", val),
-                            Err(_) => s.clone(),
-                        }
-                    };
-
-                    // Print Bids
-                    for bid in &orderbook.bids {
-                        let price = fmt_4dec(&bid[0]);
-                        let qty = fmt_4dec(&bid[1]);
-                        // Print in green
-                        println!("{}  {:>8}$  {:>8}{}", GREEN, price, qty, RESET);
-                    }
-
-                    // Blank line
-                    println!();
-
-                    // Print Asks
-                    for ask in &orderbook.asks {
-                        let price = fmt_4dec(&ask[0]);
-                        let qty = fmt_4dec(&ask[1]);
-                        // Print in red
-                        println!("{}  {:>8}$ {:>8}{}", RED, price, qty, RESET);
-                    }
-                } else {
-                    eprintln!("Error parsing JSON response.");
-                }
+        // Near the limit, or banned after a 418/429, wait instead of polling into rejections
+        if let Some(delay) = api
+            .weight()
+            .throttle_delay(weight_limit, WEIGHT_THROTTLE_FRACTION)
+        {
+            println!(
+                "Request weight {}, holding off for {:.0}s",
+                weight_gauge(api.weight().used(), weight_limit),
+                delay.as_secs_f64()
+            );
+            controls.wait(delay).await;
+            continue;
+        }
+
+        match api.get_orderbook_snapshot(symbol, limit).await {
+            Ok((orderbook, _)) => {
+                println!(
+                    "Request weight {}",
+                    weight_gauge(api.weight().used(), weight_limit)
+                );
+                render_book(&orderbook, symbol, vwap_qty, precision, view);
             }
             Err(e) => {
-                eprintln!("Request error: {}", e);
+                error!("Error fetching {} order book: {}", symbol, e);
             }
         }
     }
+
+    info!("Stopped after {} refreshes", refreshes);
+    Ok(())
 }
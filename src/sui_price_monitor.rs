@@ -1,8 +1,26 @@
-use reqwest;
+use binance_price_checker::ansi::{self, palette};
+use binance_price_checker::logging::{self, LogFormat};
+use binance_price_checker::{
+    http_client, normalize_symbol, parse_seconds, proxy, send_request, ApiError,
+    DEFAULT_CONNECT_TIMEOUT, DEFAULT_TIMEOUT,
+};
+use clap::Parser;
+use reqwest::Client;
+use reqwest::StatusCode;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
-use std::thread;
-use std::time::Duration;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use binance_price_checker::endpoint::Endpoint;
+use binance_price_checker::keyboard;
 
 #[derive(Serialize, Deserialize, Debug)]
 struct TickerPrice {
@@ -10,87 +28,650 @@ struct TickerPrice {
     price: String,
 }
 
-// ANSI color codes
-const RED: &str = "\x1b[31m";
-const GREEN: &str = "\x1b[32m";
-const RESET: &str = "\x1b[0m";
+#[derive(Deserialize, Debug)]
+struct Ticker24hr {
+    symbol: String,
+    #[serde(rename = "priceChangePercent")]
+    price_change_percent: String,
+}
+
+// Ticker endpoints, appended to --base-url (binance.com by default)
+const TICKER_PATH: &str = "/api/v3/ticker/price";
+const TICKER_24HR_PATH: &str = "/api/v3/ticker/24hr";
+const SYMBOLS: &[&str] = &["SUIUSDT"]; // Without --symbols-file
+const POLL_INTERVAL: f64 = 1.0; // Default for --interval
+
+// Keeps the encoded symbols array well under Binance's request-line limit
+const MAX_SYMBOLS_PER_REQUEST: usize = 100;
+
+// Under --only-changes, an unchanged price is reprinted this often; default for --heartbeat
+const HEARTBEAT_INTERVAL: f64 = 30.0;
+
+// Horizons shown after each price, computed from this process's own samples
+const HORIZONS: &[(&str, Duration)] = &[
+    ("1m", Duration::from_secs(60)),
+    ("5m", Duration::from_secs(5 * 60)),
+];
+// 24h change comes from /ticker/24hr, which is heavier, so refresh it sparingly
+const TICKER_24HR_REFRESH: Duration = Duration::from_secs(60);
+
+// Exponential moving averages shown after each price; default for --ema-periods
+const EMA_PERIODS: &[u32] = &[12, 26];
+
+// Prices per symbol kept for the exit statistics; default for --history-size, a day of
+// polls at POLL_INTERVAL
+const HISTORY_SIZE: NonZeroUsize = NonZeroUsize::new(86_400).unwrap();
+
+#[derive(Parser, Debug)]
+#[command(about = "Print Binance spot prices as they change")]
+struct Args {
+    /// File with one symbol per line, `#` starting a comment; SUIUSDT without it
+    #[arg(long, value_name = "PATH")]
+    symbols_file: Option<String>,
+
+    /// REST host: `com`, `us`, or an https:// base URL
+    #[arg(long, default_value = "com")]
+    base_url: Endpoint,
+
+    /// Seconds between polls
+    #[arg(long, value_name = "SECS", default_value_t = POLL_INTERVAL, value_parser = parse_seconds)]
+    interval: f64,
+
+    /// Prices per symbol kept for the exit statistics
+    #[arg(long, value_name = "N", default_value_t = HISTORY_SIZE)]
+    history_size: NonZeroUsize,
+
+    /// Comma-separated exponential moving average periods, in ticks
+    #[arg(long, value_name = "N", value_delimiter = ',', default_values_t = EMA_PERIODS.to_vec(), value_parser = clap::value_parser!(u32).range(1..))]
+    ema_periods: Vec<u32>,
+
+    /// Alert when a price crosses above PRICE; may be repeated
+    #[arg(long, value_name = "PRICE", value_parser = parse_price)]
+    alert_above: Vec<Decimal>,
+
+    /// Alert when a price crosses below PRICE; may be repeated
+    #[arg(long, value_name = "PRICE", value_parser = parse_price)]
+    alert_below: Vec<Decimal>,
+
+    /// POST each crossed alert to this URL as JSON (Slack and Discord webhooks work as is)
+    #[arg(long, value_name = "URL", value_parser = parse_webhook_url)]
+    webhook_url: Option<String>,
+
+    /// Print a price only when it differs from the previous poll
+    #[arg(long)]
+    only_changes: bool,
+
+    /// Seconds after which --only-changes reprints an unchanged price
+    #[arg(long, value_name = "SECS", default_value_t = HEARTBEAT_INTERVAL, value_parser = parse_seconds, requires = "only_changes")]
+    heartbeat: f64,
+
+    /// Seconds before a request is abandoned as timed out
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_TIMEOUT, value_parser = parse_seconds)]
+    timeout: f64,
+
+    /// Seconds allowed for establishing a connection
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_CONNECT_TIMEOUT, value_parser = parse_seconds)]
+    connect_timeout: f64,
+
+    /// Proxy for requests: http://, socks5:// or socks5h://, optionally with user:pass@;
+    /// defaults to HTTPS_PROXY or ALL_PROXY
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// Log lines as readable text or as one JSON object per event (on stderr, filtered by RUST_LOG)
+    #[arg(long, value_enum, default_value_t = LogFormat::Human)]
+    log_format: LogFormat,
+
+    /// Print without ANSI colors, as when NO_COLOR is set
+    #[arg(long)]
+    no_color: bool,
+}
+
+fn parse_price(value: &str) -> Result<Decimal, String> {
+    match value.parse::<Decimal>() {
+        Ok(price) if price > Decimal::ZERO => Ok(price),
+        Ok(_) => Err("must be a positive price".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_webhook_url(value: &str) -> Result<String, String> {
+    if value.starts_with("https://") || value.starts_with("http://") {
+        Ok(value.to_string())
+    } else {
+        Err("must be an http(s) URL".to_string())
+    }
+}
+
+// Every price of the session, up to `capacity` of the newest, summarized on exit
+struct PriceStats {
+    prices: VecDeque<Decimal>,
+    capacity: usize,
+    first: Decimal, // Kept when evicted, so the total change covers the whole session
+    observed: u64,
+}
+
+impl PriceStats {
+    fn new(capacity: usize, first: Decimal) -> PriceStats {
+        PriceStats {
+            prices: VecDeque::with_capacity(capacity.min(HISTORY_SIZE.get())),
+            capacity,
+            first,
+            observed: 0,
+        }
+    }
+
+    fn record(&mut self, price: Decimal) {
+        if self.prices.len() == self.capacity {
+            self.prices.pop_front();
+        }
+        self.prices.push_back(price);
+        self.observed += 1;
+    }
+
+    // e.g. "3600 prices, min 1.234500$ max 1.250000$ mean 1.241203$ stddev 0.003120$,
+    // +0.012300$ (+1.00%) since the first"
+    fn summary(&self) -> String {
+        let (Some(min), Some(max), Some(&last)) = (
+            self.prices.iter().min(),
+            self.prices.iter().max(),
+            self.prices.back(),
+        ) else {
+            return "no prices".to_string();
+        };
+        let values: Vec<f64> = self.prices.iter().filter_map(|p| p.to_f64()).collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let squares: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+        let stddev = (squares / values.len() as f64).sqrt();
+        let change = last - self.first;
+        let change_percent =
+            change.checked_div(self.first).unwrap_or_default() * Decimal::ONE_HUNDRED;
+        let window = if self.observed > self.prices.len() as u64 {
+            format!("last {} of {} prices", self.prices.len(), self.observed)
+        } else {
+            format!("{} prices", self.observed)
+        };
+        format!(
+            "{}, min {:.6}$ max {:.6}$ mean {:.6}$ stddev {:.6}$, \
+             {:+.6}$ ({:+.2}%) since the first",
+            window, min, max, mean, stddev, change, change_percent
+        )
+    }
+}
+
+// Exponential moving average over `period` ticks, seeded with the first price
+struct Ema {
+    period: u32,
+    value: f64,
+}
+
+impl Ema {
+    fn new(period: u32, first: f64) -> Ema {
+        Ema {
+            period,
+            value: first,
+        }
+    }
+
+    fn update(&mut self, price: f64) {
+        let alpha = 2.0 / (self.period as f64 + 1.0);
+        self.value += alpha * (price - self.value);
+    }
+}
+
+// e.g. "EMA(12) 1.234500$ above EMA(26) 1.233000$ above", green while the shortest
+// EMA is over the longest (bullish), red while it's under; with one period, by
+// where spot is against it
+fn ema_row(emas: &[Ema], spot: f64) -> String {
+    let ansi = palette();
+    let (Some(shortest), Some(longest)) = (emas.first(), emas.last()) else {
+        return String::new();
+    };
+    let (fast, slow) = if emas.len() > 1 {
+        (shortest.value, longest.value)
+    } else {
+        (spot, shortest.value)
+    };
+    let color = if fast > slow {
+        ansi.green
+    } else if fast < slow {
+        ansi.red
+    } else {
+        ansi.reset
+    };
+    let parts: Vec<String> = emas
+        .iter()
+        .map(|ema| {
+            let side = if spot >= ema.value { "above" } else { "below" };
+            format!("EMA({}) {:.6}$ {}", ema.period, ema.value, side)
+        })
+        .collect();
+    format!("{}{}{}", color, parts.join(" "), ansi.reset)
+}
+
+// `--alert-above PRICE` / `--alert-below PRICE`: fires on the tick a symbol's price
+// crosses the threshold, not on every tick past it. A symbol's first price only sets
+// its side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    Above,
+    Below,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Above => "above",
+            Direction::Below => "below",
+        }
+    }
+}
+
+struct Alert {
+    direction: Direction,
+    threshold: Decimal,
+    past: HashMap<String, bool>, // Per symbol: was the last price beyond the threshold
+}
+
+impl Alert {
+    fn new(direction: Direction, threshold: Decimal) -> Alert {
+        Alert {
+            direction,
+            threshold,
+            past: HashMap::new(),
+        }
+    }
+
+    // True when `price` has just crossed the threshold
+    fn check(&mut self, symbol: &str, price: Decimal) -> bool {
+        let past = match self.direction {
+            Direction::Above => price > self.threshold,
+            Direction::Below => price < self.threshold,
+        };
+        let was_past = self.past.insert(symbol.to_string(), past);
+        past && was_past == Some(false)
+    }
+}
+
+// POST in the background so a slow or failing webhook never holds up the loop.
+// `text` is what Slack shows and `content` what Discord does; the rest is for
+// anything else reading the payload.
+fn post_alert(client: &Client, url: &str, symbol: &str, price: Decimal, alert: &Alert) {
+    let message = format!(
+        "{} crossed {} {}$: now {}$",
+        symbol,
+        alert.direction.as_str(),
+        alert.threshold,
+        price
+    );
+    let payload = serde_json::json!({
+        "text": message,
+        "content": message,
+        "symbol": symbol,
+        "price": price.to_string(),
+        "threshold": alert.threshold.to_string(),
+        "direction": alert.direction.as_str(),
+    });
+    let request = client.post(url).json(&payload);
+    tokio::spawn(async move {
+        match request.send().await {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => warn!("Alert webhook answered {}", response.status()),
+            Err(e) => warn!("Could not deliver alert webhook: {}", e),
+        }
+    });
+}
+
+// Query a ticker endpoint for many symbols with one request per MAX_SYMBOLS_PER_REQUEST chunk
+async fn get_tickers<T: DeserializeOwned>(
+    client: &Client,
+    url: &str,
+    symbols: &[String],
+) -> Result<Vec<T>, Box<dyn Error>> {
+    let mut tickers = Vec::with_capacity(symbols.len());
+
+    for chunk in symbols.chunks(MAX_SYMBOLS_PER_REQUEST) {
+        // e.g. symbols=["BTCUSDT","ETHUSDT"], URL-encoded by reqwest
+        let symbols_param = serde_json::to_string(chunk)?;
+        let request = client.get(url).query(&[("symbols", symbols_param)]);
+        let response = send_request(request, url, "fetching prices").await?;
+        tickers.extend(response.json::<Vec<T>>().await?);
+    }
+
+    Ok(tickers)
+}
+
+async fn get_prices(
+    client: &Client,
+    base_url: &str,
+    symbols: &[String],
+) -> Result<Vec<TickerPrice>, Box<dyn Error>> {
+    let url = format!("{}{}", base_url, TICKER_PATH);
+    get_tickers(client, &url, symbols).await
+}
+
+// A batch with an unknown symbol fails as a whole with 400, so ask for each one on
+// its own to find which; a symbol whose own request also fails some other way is kept
+async fn invalid_symbols(client: &Client, base_url: &str, symbols: &[String]) -> Vec<String> {
+    let url = format!("{}{}", base_url, TICKER_PATH);
+    let mut invalid = Vec::new();
+    for symbol in symbols {
+        let request = client.get(&url).query(&[("symbol", symbol)]);
+        if let Err(e) = send_request(request, &url, "checking symbol").await {
+            if is_bad_request(e.as_ref()) {
+                invalid.push(symbol.clone());
+            }
+        }
+    }
+    invalid
+}
+
+fn is_bad_request(error: &(dyn Error + 'static)) -> bool {
+    matches!(
+        error.downcast_ref::<ApiError>(),
+        Some(ApiError::Http {
+            status: StatusCode::BAD_REQUEST,
+            ..
+        })
+    )
+}
+
+// Symbols from --symbols-file, one per line with `#` starting a comment; SYMBOLS without it
+fn load_symbols(path: Option<&str>) -> Result<Vec<String>, Box<dyn Error>> {
+    let Some(path) = path else {
+        return Ok(SYMBOLS.iter().map(|s| s.to_string()).collect());
+    };
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let mut symbols: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let symbol = line.split('#').next().unwrap_or_default().trim();
+        if symbol.is_empty() {
+            continue;
+        }
+        let symbol = normalize_symbol(symbol).map_err(|e| format!("{}: {}", path, e))?;
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    if symbols.is_empty() {
+        return Err(format!("No symbols in {}", path).into());
+    }
+    Ok(symbols)
+}
+
+// 24h price change percent per symbol
+async fn get_24hr_changes(
+    client: &Client,
+    base_url: &str,
+    symbols: &[String],
+) -> Result<HashMap<String, f64>, Box<dyn Error>> {
+    let url = format!("{}{}", base_url, TICKER_24HR_PATH);
+    let tickers: Vec<Ticker24hr> = get_tickers(client, &url, symbols).await?;
+    let mut changes = HashMap::with_capacity(tickers.len());
+    for ticker in tickers {
+        changes.insert(ticker.symbol, ticker.price_change_percent.parse::<f64>()?);
+    }
+    Ok(changes)
+}
+
+fn colored_percent(label: &str, percent: Option<f64>) -> String {
+    let ansi = palette();
+    match percent {
+        Some(p) if p > 0.0 => format!("{} {}{:+.2}%{}", label, ansi.green, p, ansi.reset),
+        Some(p) if p < 0.0 => format!("{} {}{:+.2}%{}", label, ansi.red, p, ansi.reset),
+        Some(p) => format!("{} {:+.2}%", label, p),
+        None => format!("{} --", label),
+    }
+}
+
+// e.g. "1m +0.05% 5m -0.10% 24h +3.20%", with "--" until enough history exists
+fn horizon_row(
+    samples: &VecDeque<(Instant, Decimal)>,
+    current: Decimal,
+    change_24h: Option<f64>,
+) -> String {
+    let mut parts = Vec::with_capacity(HORIZONS.len() + 1);
+    for (label, horizon) in HORIZONS {
+        // Newest sample at least `horizon` old
+        let reference = samples
+            .iter()
+            .rev()
+            .find(|(at, _)| at.elapsed() >= *horizon)
+            .map(|(_, price)| *price);
+        let percent = reference
+            .and_then(|price| (current - price).checked_div(price))
+            .and_then(|ratio| (ratio * Decimal::ONE_HUNDRED).to_f64());
+        parts.push(colored_percent(label, percent));
+    }
+    parts.push(colored_percent("24h", change_24h));
+    parts.join(" ")
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    // Binance API endpoint for ticker price
-    let url = "https://api.binance.com/api/v3/ticker/price?symbol=SUIUSDT";
+    let args = Args::parse();
+    logging::init(args.log_format, false);
+    // palette() also sees --no-color, for the helpers that call it themselves
+    let ansi = if args.no_color {
+        ansi::PLAIN
+    } else {
+        palette()
+    };
+    let client = http_client(
+        Duration::from_secs_f64(args.timeout),
+        Duration::from_secs_f64(args.connect_timeout),
+        proxy::resolve(args.proxy.as_deref())?.as_ref(),
+    )?;
+    let endpoint = args.base_url;
+    let poll_interval = Duration::from_secs_f64(args.interval);
+    let history_size = args.history_size.get();
+    // Shortest first, which ema_row relies on
+    let mut ema_periods = args.ema_periods;
+    ema_periods.sort_unstable();
+    ema_periods.dedup();
+    let mut alerts: Vec<Alert> = args
+        .alert_above
+        .iter()
+        .map(|&price| Alert::new(Direction::Above, price))
+        .chain(
+            args.alert_below
+                .iter()
+                .map(|&price| Alert::new(Direction::Below, price)),
+        )
+        .collect();
+    let webhook_url = args.webhook_url;
+    let only_changes = args.only_changes;
+    let heartbeat = Duration::from_secs_f64(args.heartbeat);
+    let mut symbols = load_symbols(args.symbols_file.as_deref())?;
+    // Pads the symbol column so several symbols line up as a table
+    let symbol_width = symbols.iter().map(String::len).max().unwrap_or_default();
 
-    println!("Monitoring SUI/USDT price from Binance...");
+    println!("Monitoring {} price from Binance...", symbols.join(", "));
     println!("Press Ctrl+C to exit");
     println!("----------------------------------------");
 
-    let mut previous_price: Option<f64> = None;
-
-    loop {
-        // Make the HTTP request
-        match reqwest::get(url).await {
-            Ok(response) => {
-                // Check if the request was successful
-                if response.status().is_success() {
-                    // Parse the JSON response
-                    match response.json::<TickerPrice>().await {
-                        Ok(ticker) => {
-                            // Parse the current price
-                            match ticker.price.parse::<f64>() {
-                                Ok(current_price) => {
-                                    let timestamp =
-                                        chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-
-                                    // Calculate percentage change if we have a previous price
-                                    if let Some(prev_price) = previous_price {
-                                        let change = current_price - prev_price;
-                                        let change_percent = (change / prev_price) * 100.0;
-
-                                        // Determine color based on price movement
-                                        let color = if current_price > prev_price {
-                                            GREEN
-                                        } else if current_price < prev_price {
-                                            RED
-                                        } else {
-                                            RESET
-                                        };
+    let mut previous_prices: HashMap<String, Decimal> = HashMap::new();
+    // Last line actually printed per symbol; drives the --only-changes heartbeat
+    let mut last_printed: HashMap<String, (Decimal, Instant)> = HashMap::new();
+    let mut samples: HashMap<String, VecDeque<(Instant, Decimal)>> = HashMap::new();
+    let mut stats: HashMap<String, PriceStats> = HashMap::new();
+    let mut emas: HashMap<String, Vec<Ema>> = HashMap::new();
+    let longest_horizon = HORIZONS.iter().map(|(_, h)| *h).max().unwrap_or_default();
+
+    let mut changes_24h: HashMap<String, f64> = HashMap::new();
+    let mut last_24hr_refresh: Option<Instant> = None;
+
+    let controls = Arc::new(keyboard::Controls::default());
+    keyboard::watch_ctrl_c(controls.clone());
+    let mut polls: u64 = 0;
+
+    while !controls.is_shutting_down() {
+        polls += 1;
+        if last_24hr_refresh.is_none_or(|t| t.elapsed() >= TICKER_24HR_REFRESH) {
+            last_24hr_refresh = Some(Instant::now());
+            match get_24hr_changes(&client, endpoint.url(), &symbols).await {
+                Ok(changes) => changes_24h = changes,
+                Err(e) => warn!("Error fetching 24h change: {}", e),
+            }
+        }
+
+        // One batched request covers every symbol
+        match get_prices(&client, endpoint.url(), &symbols).await {
+            Ok(mut tickers) => {
+                tickers.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+                if symbols.len() > 1 {
+                    println!();
+                }
+                for ticker in tickers {
+                    // Parse the current price
+                    match ticker.price.parse::<Decimal>() {
+                        Ok(current_price) => {
+                            let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+                            stats
+                                .entry(ticker.symbol.clone())
+                                .or_insert_with(|| PriceStats::new(history_size, current_price))
+                                .record(current_price);
+                            let spot = current_price.to_f64().unwrap_or(f64::NAN);
+                            let symbol_emas =
+                                emas.entry(ticker.symbol.clone()).or_insert_with(|| {
+                                    ema_periods
+                                        .iter()
+                                        .map(|&period| Ema::new(period, spot))
+                                        .collect()
+                                });
+                            symbol_emas.iter_mut().for_each(|ema| ema.update(spot));
+                            let ema_line = ema_row(symbol_emas, spot);
+                            for alert in &mut alerts {
+                                if !alert.check(&ticker.symbol, current_price) {
+                                    continue;
+                                }
+                                println!(
+                                    "{}{}[{}] ALERT {} crossed {} {}${}",
+                                    ansi.bold,
+                                    ansi.yellow,
+                                    timestamp,
+                                    ticker.symbol,
+                                    alert.direction.as_str(),
+                                    alert.threshold,
+                                    ansi.reset
+                                );
+                                if let Some(url) = &webhook_url {
+                                    post_alert(&client, url, &ticker.symbol, current_price, alert);
+                                }
+                            }
+
+                            // Keep just enough history to cover the longest horizon
+                            let history = samples.entry(ticker.symbol.clone()).or_default();
+                            history.push_back((Instant::now(), current_price));
+                            while history.len() > 1 && history[1].0.elapsed() >= longest_horizon {
+                                history.pop_front();
+                            }
+                            let horizons = horizon_row(
+                                history,
+                                current_price,
+                                changes_24h.get(&ticker.symbol).copied(),
+                            );
 
+                            let unchanged =
+                                previous_prices.get(&ticker.symbol) == Some(&current_price);
+                            if only_changes && unchanged {
+                                if let Some((printed_price, printed_at)) =
+                                    last_printed.get_mut(&ticker.symbol)
+                                {
+                                    if printed_at.elapsed() >= heartbeat {
                                         println!(
-                                            "[{}] {}: {}{:.6}$ ({:+.6}$, {:+.2}%){}",
+                                            "[{}] {:<width$}: still {:.6}$",
                                             timestamp,
                                             ticker.symbol,
-                                            color,
-                                            current_price,
-                                            change,
-                                            change_percent,
-                                            RESET
-                                        );
-                                    } else {
-                                        // First run, no previous price to compare
-                                        println!(
-                                            "[{}] {}: ${:.6}",
-                                            timestamp, ticker.symbol, current_price
+                                            printed_price,
+                                            width = symbol_width
                                         );
+                                        *printed_at = Instant::now();
                                     }
-
-                                    // Update previous price for next iteration
-                                    previous_price = Some(current_price);
                                 }
-                                Err(e) => println!("Error parsing price: {}", e),
+                                continue;
+                            }
+
+                            // Calculate percentage change if we have a previous price
+                            if let Some(&prev_price) = previous_prices.get(&ticker.symbol) {
+                                let change = current_price - prev_price;
+                                let change_percent =
+                                    change.checked_div(prev_price).unwrap_or_default()
+                                        * Decimal::ONE_HUNDRED;
+
+                                // Determine color based on price movement
+                                let color = if current_price > prev_price {
+                                    ansi.green
+                                } else if current_price < prev_price {
+                                    ansi.red
+                                } else {
+                                    ansi.reset
+                                };
+
+                                println!(
+                                    "[{}] {:<width$}: {}{:.6}$ ({:+.6}$, {:+.2}%){} | {} | {}",
+                                    timestamp,
+                                    ticker.symbol,
+                                    color,
+                                    current_price,
+                                    change,
+                                    change_percent,
+                                    ansi.reset,
+                                    horizons,
+                                    ema_line,
+                                    width = symbol_width
+                                );
+                            } else {
+                                // First run, no previous price to compare
+                                println!(
+                                    "[{}] {:<width$}: ${:.6} | {} | {}",
+                                    timestamp,
+                                    ticker.symbol,
+                                    current_price,
+                                    horizons,
+                                    ema_line,
+                                    width = symbol_width
+                                );
                             }
+
+                            // Update previous price for next iteration
+                            last_printed
+                                .insert(ticker.symbol.clone(), (current_price, Instant::now()));
+                            previous_prices.insert(ticker.symbol, current_price);
                         }
-                        Err(e) => println!("Error parsing JSON: {}", e),
+                        Err(e) => error!("Error parsing price for {}: {}", ticker.symbol, e),
                     }
-                } else {
-                    println!(
-                        "Error: Failed to fetch price. Status code: {}",
-                        response.status()
-                    );
                 }
             }
-            Err(e) => println!("Request error: {}", e),
+            Err(e) if is_bad_request(e.as_ref()) => {
+                let invalid = invalid_symbols(&client, endpoint.url(), &symbols).await;
+                if invalid.is_empty() {
+                    error!("Error: {}", e);
+                }
+                for symbol in &invalid {
+                    warn!("Dropping {}: Binance doesn't know the symbol", symbol);
+                }
+                symbols.retain(|symbol| !invalid.contains(symbol));
+                if symbols.is_empty() {
+                    return Err("None of the symbols are valid".into());
+                }
+            }
+            Err(e) => error!("Error: {}", e),
         }
 
-        //thread::sleep(Duration::from_secs(1));
+        // Also reached after errors, so a failing request can't become a tight retry loop.
+        // Returns early on Ctrl+C.
+        controls.wait(poll_interval).await;
+    }
+
+    info!("Stopped after {} polls", polls);
+    println!("----------------------------------------");
+    for symbol in &symbols {
+        match stats.get(symbol) {
+            Some(stats) => println!("{}: {}", symbol, stats.summary()),
+            None => println!("{}: no prices", symbol),
+        }
     }
+    Ok(())
 }
@@ -0,0 +1,63 @@
+// Column-aligned rendering and number formatting for the terminal book views.
+//
+// Widths are measured from the visible text of each cell, so ANSI color codes
+// embedded in a cell don't push its column out of line.
+
+/// Number of characters `s` occupies on screen, skipping ANSI escape sequences.
+pub fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // CSI sequences end at the first byte in '@'..='~'
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) && c != '[' {
+                    break;
+                }
+            }
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// Quantity with a K/M/B suffix once it reaches a thousand, e.g. `1523000.0` ->
+/// `1.52M`. Smaller quantities keep four decimals like the unsuffixed views.
+pub fn human_qty(qty: f64) -> String {
+    let magnitude = qty.abs();
+    if magnitude >= 1e9 {
+        format!("{:.2}B", qty / 1e9)
+    } else if magnitude >= 1e6 {
+        format!("{:.2}M", qty / 1e6)
+    } else if magnitude >= 1e3 {
+        format!("{:.2}K", qty / 1e3)
+    } else {
+        format!("{:.4}", qty)
+    }
+}
+
+/// Right-align every cell to the widest visible value in its column and join
+/// the cells of each row with `separator`.
+pub fn render_rows(rows: &[Vec<String>], separator: &str) -> Vec<String> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut widths = vec![0; columns];
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(visible_width(cell));
+        }
+    }
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .map(|(i, cell)| {
+                    let padding = widths[i] - visible_width(cell);
+                    format!("{}{}", " ".repeat(padding), cell)
+                })
+                .collect::<Vec<_>>()
+                .join(separator)
+        })
+        .collect()
+}
@@ -0,0 +1,263 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{error::Error, fs};
+
+const USAGE: &str = "Usage: validate-import FILE [--rewrite OUT_FILE]";
+
+// Fields every snapshot must carry to be usable
+const REQUIRED_FIELDS: &[&str] = &[
+    "lastUpdateId",
+    "bids",
+    "asks",
+    "current_price",
+    "local_timestamp",
+    "local_datetime",
+];
+// Fields main.rs writes that older or external captures may lack
+const OPTIONAL_FIELDS: &[&str] = &[
+    "depth_limit",
+    "saved_levels",
+    "fetch_latency_ms",
+    "source",
+    "sequence",
+    "best_bid",
+    "best_ask",
+    "spread",
+    "spread_bps",
+    "mid_price",
+    "ticker_24h",
+    "price_bins",
+    "imbalance",
+    "cumulative",
+    "tick_move_outlier",
+    "layers",
+];
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct PriceData {
+    price: String,
+    timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct FetchLatency {
+    orderbook: f64,
+    price: f64,
+}
+
+// CombinedData as written by main.rs. Unknown fields are ignored when reading
+// (reported separately) and missing ones fall back to defaults for --rewrite.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct CombinedData {
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: u64,
+    bids: Vec<[String; 2]>,
+    asks: Vec<[String; 2]>,
+    current_price: PriceData,
+    local_timestamp: u64,
+    local_datetime: String,
+    depth_limit: u32,
+    saved_levels: usize,
+    fetch_latency_ms: FetchLatency,
+    source: String,
+    sequence: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    best_bid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    best_ask: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spread: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spread_bps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mid_price: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticker_24h: Option<Ticker24h>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    price_bins: Option<PriceBins>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    imbalance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cumulative: Option<CumulativeDepth>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tick_move_outlier: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    layers: Option<Layers>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resync: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Ticker24h {
+    price_change_percent: String,
+    high_price: String,
+    low_price: String,
+    volume: String,
+    quote_volume: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct PriceBins {
+    span_bps: f64,
+    bids: Vec<f64>,
+    asks: Vec<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct CumulativeDepth {
+    bids: Vec<[String; 2]>,
+    asks: Vec<[String; 2]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct Layers {
+    l5: Layer,
+    l20: Layer,
+    l100: Layer,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct Layer {
+    levels: usize,
+    vwap_spread: Option<f64>,
+    imbalance: Option<f64>,
+}
+
+// How the records were laid out in the input, so --rewrite can preserve it
+enum Layout {
+    Single,
+    Array,
+    Lines,
+}
+
+#[derive(Default)]
+struct RecordReport {
+    missing: Vec<String>,
+    extra: Vec<String>,
+    error: Option<String>,
+}
+
+impl RecordReport {
+    fn is_malformed(&self) -> bool {
+        !self.missing.is_empty() || self.error.is_some()
+    }
+}
+
+fn read_records(data: &str) -> Result<(Layout, Vec<Value>), Box<dyn Error>> {
+    match serde_json::from_str::<Value>(data) {
+        Ok(Value::Array(records)) => Ok((Layout::Array, records)),
+        Ok(record) => Ok((Layout::Single, vec![record])),
+        // Not a single JSON document; try one record per line
+        Err(_) => {
+            let records = data
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str::<Value>)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((Layout::Lines, records))
+        }
+    }
+}
+
+fn check_record(record: &Value) -> (RecordReport, Option<CombinedData>) {
+    let mut report = RecordReport::default();
+    let Some(object) = record.as_object() else {
+        report.error = Some("record is not a JSON object".to_string());
+        return (report, None);
+    };
+
+    report.missing = REQUIRED_FIELDS
+        .iter()
+        .filter(|field| !object.contains_key(**field))
+        .map(|field| field.to_string())
+        .collect();
+    report.extra = object
+        .keys()
+        .filter(|key| !REQUIRED_FIELDS.contains(&key.as_str()))
+        .filter(|key| !OPTIONAL_FIELDS.contains(&key.as_str()))
+        .cloned()
+        .collect();
+
+    match serde_json::from_value::<CombinedData>(record.clone()) {
+        Ok(snapshot) => (report, Some(snapshot)),
+        Err(e) => {
+            report.error = Some(e.to_string());
+            (report, None)
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let input = args.next().ok_or(USAGE)?;
+    let rewrite = match args.next().as_deref() {
+        Some("--rewrite") => Some(args.next().ok_or(USAGE)?),
+        Some(other) => return Err(format!("Unexpected argument '{}'\n{}", other, USAGE).into()),
+        None => None,
+    };
+
+    let data = fs::read_to_string(&input)?;
+    let (layout, records) = read_records(&data)?;
+
+    let mut malformed = 0;
+    let mut canonical = Vec::with_capacity(records.len());
+    for (index, record) in records.iter().enumerate() {
+        let (report, snapshot) = check_record(record);
+
+        if report.is_malformed() {
+            malformed += 1;
+        }
+        if !report.missing.is_empty() {
+            println!("record {}: missing {}", index, report.missing.join(", "));
+        }
+        if !report.extra.is_empty() {
+            println!("record {}: extra {}", index, report.extra.join(", "));
+        }
+        if let Some(error) = &report.error {
+            println!("record {}: invalid: {}", index, error);
+        }
+
+        canonical.extend(snapshot);
+    }
+
+    println!(
+        "{}: {} records, {} malformed",
+        input,
+        records.len(),
+        malformed
+    );
+
+    if let Some(output) = rewrite {
+        // Records that failed to deserialize can't be filled in and are dropped
+        let serialized = match layout {
+            Layout::Single => match canonical.first() {
+                Some(snapshot) => serde_json::to_string_pretty(snapshot)?,
+                None => return Err("Nothing to rewrite: the record is invalid".into()),
+            },
+            Layout::Array => serde_json::to_string_pretty(&canonical)?,
+            Layout::Lines => {
+                let mut lines = String::new();
+                for snapshot in &canonical {
+                    lines.push_str(&serde_json::to_string(snapshot)?);
+                    lines.push('\n');
+                }
+                lines
+            }
+        };
+        fs::write(&output, serialized)?;
+        println!("Wrote {} canonical records to {}", canonical.len(), output);
+    }
+
+    if malformed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
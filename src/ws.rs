@@ -0,0 +1,336 @@
+// Live order book kept in sync from Binance's diff depth stream (main's `--mode ws`),
+// following the documented procedure:
+//   1. open <symbol>@depth; events queue up on the socket until read
+//   2. fetch a REST depth snapshot
+//   3. drop events with u <= the snapshot's lastUpdateId
+//   4. the first applied event must span lastUpdateId + 1 (U <= id + 1 <= u)
+//   5. every later event must start right after the previous one (U == previous u + 1)
+// A gap, parse failure or disconnect discards the book and starts over at step 1.
+// lastUpdateId jumps across such a restart, so the first read after it is marked.
+
+use crate::{jittered, proxy, BinanceClient, Level, OrderBook};
+use futures_util::StreamExt;
+use reqwest::Url;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::info;
+
+// Snapshot depth used to seed the local book; reads are trimmed to --depth-limit
+const SNAPSHOT_LIMIT: u32 = 1000;
+const RECONNECT_BASE_DELAY: f64 = 0.5; // Seconds, doubling after each failed attempt
+const RECONNECT_MAX_DELAY: f64 = 30.0;
+
+/// One `<symbol>@depth` message: every level that changed between updates U and u.
+#[derive(Deserialize, Debug)]
+pub struct DepthEvent {
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    #[serde(rename = "b")]
+    pub bids: Vec<Level>,
+    #[serde(rename = "a")]
+    pub asks: Vec<Level>,
+}
+
+/// An order book kept locally from a REST depth snapshot and the diff stream's
+/// events, following steps 3-5 above.
+pub struct LocalBook {
+    last_update_id: u64,
+    // Levels keyed by price so the maps iterate in price order
+    bids: BTreeMap<Decimal, Level>,
+    asks: BTreeMap<Decimal, Level>,
+    seeded: bool, // No event applied since the snapshot yet
+}
+
+impl LocalBook {
+    pub fn from_snapshot(snapshot: OrderBook) -> LocalBook {
+        let mut book = LocalBook {
+            last_update_id: snapshot.last_update_id,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            seeded: true,
+        };
+        update_levels(&mut book.bids, snapshot.bids);
+        update_levels(&mut book.asks, snapshot.asks);
+        book
+    }
+
+    pub fn last_update_id(&self) -> u64 {
+        self.last_update_id
+    }
+
+    /// Apply the next event, returning false if it predates the book and was dropped.
+    /// An event that leaves a gap is an error; the book has to be rebuilt from a new
+    /// snapshot.
+    pub fn apply(&mut self, event: DepthEvent) -> Result<bool, String> {
+        if event.final_update_id <= self.last_update_id {
+            return Ok(false);
+        }
+        let expected = self.last_update_id + 1;
+        let in_sequence = if self.seeded {
+            event.first_update_id <= expected
+        } else {
+            event.first_update_id == expected
+        };
+        if !in_sequence {
+            return Err(format!(
+                "gap in updates (expected {}, got {}..{})",
+                expected, event.first_update_id, event.final_update_id
+            ));
+        }
+
+        update_levels(&mut self.bids, event.bids);
+        update_levels(&mut self.asks, event.asks);
+        self.last_update_id = event.final_update_id;
+        self.seeded = false;
+        Ok(true)
+    }
+
+    /// Best `limit` levels per side, bids high to low and asks low to high like
+    /// /api/v3/depth.
+    pub fn to_orderbook(&self, limit: u32) -> OrderBook {
+        let limit = limit as usize;
+        OrderBook {
+            last_update_id: self.last_update_id,
+            bids: self.bids.values().rev().take(limit).cloned().collect(),
+            asks: self.asks.values().take(limit).cloned().collect(),
+        }
+    }
+}
+
+// A zero quantity removes the level
+fn update_levels(side: &mut BTreeMap<Decimal, Level>, levels: Vec<Level>) {
+    for level in levels {
+        if level.qty.is_zero() {
+            side.remove(&level.price);
+        } else {
+            side.insert(level.price, level);
+        }
+    }
+}
+
+/// One read of the streamed book.
+pub struct StreamRead {
+    pub orderbook: OrderBook,
+    /// No updates since the previous read.
+    pub unchanged: bool,
+    /// First read since the stream reconnected and synced again, so lastUpdateId
+    /// isn't continuous with the previous read.
+    pub resync: bool,
+}
+
+// Shared between the stream task and the capture loop
+pub struct DepthBook {
+    source: String,
+    book: Mutex<Option<LocalBook>>,
+    last_read: Mutex<Option<u64>>,
+    synced_before: AtomicBool,
+    resync_pending: AtomicBool, // A later sync that no read has reported yet
+}
+
+impl DepthBook {
+    /// Stream host the book comes from, for the snapshot's `source` field.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Top `limit` levels, or None while the stream is (re)syncing.
+    pub fn read(&self, limit: u32) -> Option<StreamRead> {
+        let orderbook = self.book.lock().ok()?.as_ref()?.to_orderbook(limit);
+        let mut last_read = self.last_read.lock().ok()?;
+        let unchanged = *last_read == Some(orderbook.last_update_id);
+        *last_read = Some(orderbook.last_update_id);
+        Some(StreamRead {
+            orderbook,
+            unchanged,
+            resync: self.resync_pending.swap(false, Ordering::SeqCst),
+        })
+    }
+
+    fn apply(&self, event: DepthEvent) -> Result<(), String> {
+        let mut book = self.book.lock().map_err(|e| e.to_string())?;
+        match book.as_mut() {
+            Some(book) => book.apply(event).map(|_| ()),
+            None => Err("book was cleared mid-stream".to_string()),
+        }
+    }
+
+    fn set(&self, book: LocalBook) {
+        // Flagged before the book is visible, so the first read of it sees the flag
+        if self.synced_before.swap(true, Ordering::SeqCst) {
+            self.resync_pending.store(true, Ordering::SeqCst);
+        }
+        if let Ok(mut current) = self.book.lock() {
+            *current = Some(book);
+        }
+    }
+
+    // Drop the book until the next sync; true if there was one
+    fn clear(&self) -> bool {
+        self.book
+            .lock()
+            .map(|mut book| book.take().is_some())
+            .unwrap_or(false)
+    }
+}
+
+/// Start maintaining `symbol`'s book in the background, reconnecting forever. The
+/// stream goes through `proxy` when there is one; each disconnect is passed to
+/// `report` before reconnecting.
+pub fn spawn(
+    api: BinanceClient,
+    stream_base: &str,
+    symbol: &str,
+    proxy: Option<Url>,
+    report: fn(String),
+) -> Arc<DepthBook> {
+    let depth_book = Arc::new(DepthBook {
+        source: stream_base.trim_start_matches("wss://").to_string(),
+        book: Mutex::new(None),
+        last_read: Mutex::new(None),
+        synced_before: AtomicBool::new(false),
+        resync_pending: AtomicBool::new(false),
+    });
+    let stream_url = format!(
+        "{}/ws/{}@depth",
+        stream_base.trim_end_matches('/'),
+        symbol.to_lowercase()
+    );
+    tokio::spawn(run_stream(
+        api,
+        stream_url,
+        symbol.to_string(),
+        proxy,
+        depth_book.clone(),
+        report,
+    ));
+    depth_book
+}
+
+async fn run_stream(
+    api: BinanceClient,
+    stream_url: String,
+    symbol: String,
+    proxy: Option<Url>,
+    depth_book: Arc<DepthBook>,
+    report: fn(String),
+) {
+    let mut backoff = RECONNECT_BASE_DELAY;
+    loop {
+        let error =
+            match follow_stream(&api, &stream_url, proxy.as_ref(), &symbol, &depth_book).await {
+                Ok(()) => format!("{} depth stream closed", symbol),
+                Err(e) => format!("{} depth stream: {}", symbol, e),
+            };
+        // A connection that got in sync starts the backoff schedule over
+        if depth_book.clear() {
+            backoff = RECONNECT_BASE_DELAY;
+        }
+
+        let delay = jittered(backoff);
+        report(format!(
+            "{}, reconnecting in {:.1}s",
+            error,
+            delay.as_secs_f64()
+        ));
+        sleep(delay).await;
+        backoff = (backoff * 2.0).min(RECONNECT_MAX_DELAY);
+    }
+}
+
+// Connect, sync against a snapshot and apply events until the stream ends, a gap
+// shows up or something fails.
+async fn follow_stream(
+    api: &BinanceClient,
+    stream_url: &str,
+    proxy: Option<&Url>,
+    symbol: &str,
+    depth_book: &DepthBook,
+) -> Result<(), String> {
+    let (mut stream, _) = proxy::connect_websocket(stream_url, proxy)
+        .await
+        .map_err(|e| format!("connecting to {}: {}", stream_url, e))?;
+    // Owned here until the first event lines up, then handed to depth_book
+    let mut seeded = Some(
+        fetch_snapshot(api, symbol)
+            .await
+            .map_err(|e| format!("fetching snapshot: {}", e))?,
+    );
+
+    while let Some(message) = stream.next().await {
+        let text = match message.map_err(|e| e.to_string())? {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let event: DepthEvent =
+            serde_json::from_str(&text).map_err(|e| format!("unparseable event: {}", e))?;
+
+        match seeded.take() {
+            Some(mut book) => {
+                if !book.apply(event)? {
+                    seeded = Some(book);
+                    continue;
+                }
+                let last_update_id = book.last_update_id();
+                depth_book.set(book);
+                info!(
+                    "{} depth stream in sync at update {}",
+                    symbol, last_update_id
+                );
+            }
+            None => depth_book.apply(event)?,
+        }
+    }
+    Ok(())
+}
+
+async fn fetch_snapshot(api: &BinanceClient, symbol: &str) -> Result<LocalBook, String> {
+    match api.get_orderbook_snapshot(symbol, SNAPSHOT_LIMIT).await {
+        Ok((snapshot, _)) => Ok(LocalBook::from_snapshot(snapshot)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(last_update_id: u64) -> LocalBook {
+        LocalBook::from_snapshot(OrderBook {
+            last_update_id,
+            bids: Vec::new(),
+            asks: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn first_read_after_a_resync_is_marked() {
+        let depth_book = DepthBook {
+            source: "test".to_string(),
+            book: Mutex::new(None),
+            last_read: Mutex::new(None),
+            synced_before: AtomicBool::new(false),
+            resync_pending: AtomicBool::new(false),
+        };
+        assert!(depth_book.read(5).is_none());
+
+        // The initial sync has nothing to be discontinuous with
+        depth_book.set(snapshot(100));
+        assert!(!depth_book.read(5).unwrap().resync);
+
+        assert!(depth_book.clear());
+        assert!(depth_book.read(5).is_none());
+        depth_book.set(snapshot(500));
+        let read = depth_book.read(5).unwrap();
+        assert_eq!(read.orderbook.last_update_id, 500);
+        assert!(read.resync);
+        assert!(!depth_book.read(5).unwrap().resync);
+    }
+}
@@ -0,0 +1,112 @@
+// `--ws-serve-addr`: every accepted snapshot pushed to WebSocket clients as it is
+// produced, one text frame per snapshot holding the CombinedData record in the
+// on-disk JSON format (compact rather than pretty-printed). current_price.symbol
+// tells the symbols apart. Connect to ws://ADDR/ with any path.
+//
+// The capture loop never waits on clients: it serializes each record once into a
+// broadcast channel. A client that falls CHANNEL_CAPACITY snapshots behind, or
+// that doesn't take a frame within SEND_TIMEOUT, is disconnected.
+
+use crate::CombinedData;
+use futures_util::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, oneshot};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+const CHANNEL_CAPACITY: usize = 64; // Snapshots a client may fall behind before it's dropped
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The capture loop's end of the channel; each client subscribes its own receiver.
+pub type Subscribers = broadcast::Sender<Arc<str>>;
+
+pub fn channel() -> Subscribers {
+    broadcast::channel(CHANNEL_CAPACITY).0
+}
+
+/// Send `data` to every connected client.
+pub fn publish(subscribers: &Subscribers, data: &CombinedData) {
+    if subscribers.receiver_count() == 0 {
+        return; // Don't serialize for nobody
+    }
+    match serde_json::to_string(data) {
+        Ok(json) => {
+            let _ = subscribers.send(json.into());
+        }
+        Err(e) => warn!("Could not serialize snapshot for subscribers: {}", e),
+    }
+}
+
+/// Bind `addr` and accept clients until `shutdown` fires (or its sender is dropped).
+/// Connected clients are closed once every `subscribers` sender is gone, so drop the
+/// capture loop's before awaiting the handle.
+pub async fn serve(
+    addr: SocketAddr,
+    subscribers: Subscribers,
+    mut shutdown: oneshot::Receiver<()>,
+) -> std::io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Pushing snapshots to WebSocket clients on ws://{}/", addr);
+    Ok(tokio::spawn(async move {
+        let mut clients = JoinSet::new();
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, peer)) => {
+                        clients.spawn(client(stream, peer, subscribers.subscribe()));
+                    }
+                    Err(e) => warn!("WebSocket accept failed: {}", e),
+                },
+                // Reap finished clients so the set doesn't grow with every connection
+                Some(_) = clients.join_next(), if !clients.is_empty() => {}
+                _ = &mut shutdown => break,
+            }
+        }
+        drop(subscribers);
+        while clients.join_next().await.is_some() {}
+    }))
+}
+
+// Forward snapshots to one client until it disconnects, lags or the channel closes
+async fn client(stream: TcpStream, peer: SocketAddr, mut snapshots: broadcast::Receiver<Arc<str>>) {
+    let mut socket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            debug!("WebSocket handshake with {} failed: {}", peer, e);
+            return;
+        }
+    };
+    info!("WebSocket subscriber {} connected", peer);
+    let reason = loop {
+        tokio::select! {
+            snapshot = snapshots.recv() => match snapshot {
+                Ok(json) => {
+                    let frame = Message::Text(json.to_string());
+                    match tokio::time::timeout(SEND_TIMEOUT, socket.send(frame)).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => break e.to_string(),
+                        Err(_) => break format!("not reading, dropped after {}s", SEND_TIMEOUT.as_secs()),
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    break format!("{} snapshots behind, dropped", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    let _ = socket.close(None).await;
+                    break "shutting down".to_string();
+                }
+            },
+            // Reading answers pings; anything else from the client is ignored
+            message = socket.next() => match message {
+                Some(Ok(Message::Close(_))) | None => break "disconnected".to_string(),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => break e.to_string(),
+            },
+        }
+    };
+    info!("WebSocket subscriber {} closed: {}", peer, reason);
+}
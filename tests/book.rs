@@ -0,0 +1,244 @@
+// Pure order book helpers: top of book, VWAP, cumulative depth, imbalance,
+// validation, diffs and the streamed local book
+
+use binance_price_checker::ws::{DepthEvent, LocalBook};
+use binance_price_checker::{
+    bin_by_distance, book_imbalance, book_layer, depth_imbalance, diff_orderbooks,
+    liquidity_within, mid_price, spread, spread_bps, validate_orderbook, vwap_for_quantity,
+    with_cumulative, Level, OrderBook, Side, ValidationError, Weight,
+};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+fn decimal(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+}
+
+fn level(price: &str, qty: &str) -> Level {
+    Level {
+        price: decimal(price),
+        qty: decimal(qty),
+    }
+}
+
+fn book(bids: &[(&str, &str)], asks: &[(&str, &str)]) -> OrderBook {
+    OrderBook {
+        last_update_id: 1,
+        bids: bids.iter().map(|(p, q)| level(p, q)).collect(),
+        asks: asks.iter().map(|(p, q)| level(p, q)).collect(),
+    }
+}
+
+fn sample() -> OrderBook {
+    book(
+        &[("1.0000", "10"), ("0.9990", "20"), ("0.9980", "30")],
+        &[("1.0010", "5"), ("1.0020", "15"), ("1.0030", "25")],
+    )
+}
+
+#[test]
+fn mid_and_spread() {
+    let book = sample();
+    assert_eq!(mid_price(&book), Some(decimal("1.0005")));
+    assert_eq!(spread(&book), Some(decimal("0.0010")));
+    let bps = spread_bps(&book).unwrap();
+    assert!((bps - 9.995).abs() < 1e-3, "{}", bps);
+}
+
+#[test]
+fn mid_and_spread_need_both_sides() {
+    let one_sided = book(&[("1.0", "1")], &[]);
+    assert_eq!(mid_price(&one_sided), None);
+    assert_eq!(spread(&one_sided), None);
+    assert_eq!(spread_bps(&one_sided), None);
+}
+
+#[test]
+fn crossed_spread_is_negative() {
+    let crossed = book(&[("1.01", "1")], &[("1.00", "1")]);
+    assert_eq!(spread(&crossed), Some(decimal("-0.01")));
+}
+
+#[test]
+fn vwap_walks_levels() {
+    // 5 at 1.0010 and 10 of the next 15 at 1.0020
+    let vwap = vwap_for_quantity(&sample(), Side::Ask, decimal("15")).unwrap();
+    assert!(vwap.complete);
+    assert_eq!(vwap.filled_qty, decimal("15"));
+    assert_eq!(
+        vwap.avg_price,
+        (decimal("5") * decimal("1.0010") + decimal("10") * decimal("1.0020")) / decimal("15")
+    );
+}
+
+#[test]
+fn vwap_within_top_level_is_its_price() {
+    let vwap = vwap_for_quantity(&sample(), Side::Bid, decimal("4")).unwrap();
+    assert_eq!(vwap.avg_price, decimal("1.0000"));
+    assert!(vwap.complete);
+}
+
+#[test]
+fn vwap_partial_fill_on_thin_book() {
+    let vwap = vwap_for_quantity(&sample(), Side::Bid, decimal("100")).unwrap();
+    assert!(!vwap.complete);
+    assert_eq!(vwap.filled_qty, decimal("60"));
+}
+
+#[test]
+fn vwap_on_empty_side() {
+    let empty = book(&[("1.0", "1")], &[]);
+    assert_eq!(vwap_for_quantity(&empty, Side::Ask, decimal("1")), None);
+}
+
+#[test]
+fn cumulative_totals() {
+    let asks = with_cumulative(&sample(), Side::Ask);
+    let totals: Vec<Decimal> = asks.iter().map(|l| l.cum_qty).collect();
+    assert_eq!(totals, [decimal("5"), decimal("20"), decimal("45")]);
+    assert_eq!(asks[1].cum_notional, decimal("5.005") + decimal("15.030"));
+    assert_eq!(asks[2].level, level("1.0030", "25"));
+}
+
+#[test]
+fn imbalance() {
+    // Top 2: 30 of bids against 20 of asks
+    assert!((depth_imbalance(&sample(), 2) - 0.6).abs() < 1e-12);
+    assert_eq!(depth_imbalance(&book(&[], &[]), 10), 0.5);
+    assert_eq!(depth_imbalance(&book(&[("1", "3")], &[]), 10), 1.0);
+}
+
+#[test]
+fn weighted_imbalance() {
+    // 60 of bids against 45 of asks
+    let imbalance = book_imbalance(&sample(), Weight::Base).unwrap();
+    assert!((imbalance - 15.0 / 105.0).abs() < 1e-12);
+    assert!(book_imbalance(&sample(), Weight::Quote).unwrap() < imbalance);
+    assert_eq!(book_imbalance(&book(&[], &[]), Weight::Base), None);
+}
+
+#[test]
+fn liquidity_near_mid() {
+    // 10 bps of 1.0005 reaches only the top level on each side
+    let book = sample();
+    assert_eq!(liquidity_within(&book, Side::Bid, 10.0), Some(10.0));
+    assert_eq!(liquidity_within(&book, Side::Ask, 10.0), Some(5.0));
+    assert_eq!(liquidity_within(&book, Side::Bid, 100.0), Some(60.0));
+    let one_sided = self::book(&[("1", "3")], &[]);
+    assert_eq!(liquidity_within(&one_sided, Side::Bid, 10.0), None);
+}
+
+#[test]
+fn bins_drop_levels_beyond_the_span() {
+    // Two 10 bps bins; the third level on each side is about 25 bps out
+    let bins = bin_by_distance(&sample(), 2, 20.0).unwrap();
+    assert_eq!(bins.bids, [10.0, 20.0]);
+    assert_eq!(bins.asks, [5.0, 15.0]);
+    assert_eq!(bin_by_distance(&book(&[], &[("1", "3")]), 2, 20.0), None);
+}
+
+#[test]
+fn layer_of_the_top_level() {
+    let layer = book_layer(&sample(), 1, Weight::Base);
+    assert_eq!(layer.levels, 1);
+    assert!((layer.vwap_spread.unwrap() - 0.001).abs() < 1e-12);
+    assert!((layer.imbalance.unwrap() - 1.0 / 3.0).abs() < 1e-12);
+
+    let empty = book_layer(&book(&[], &[]), 5, Weight::Base);
+    assert_eq!(
+        (empty.levels, empty.vwap_spread, empty.imbalance),
+        (0, None, None)
+    );
+}
+
+#[test]
+fn valid_book_passes() {
+    assert_eq!(validate_orderbook(&sample()), Ok(()));
+    assert_eq!(validate_orderbook(&book(&[], &[])), Ok(()));
+}
+
+#[test]
+fn validation_errors() {
+    let zero_qty = book(&[("1.0", "0")], &[]);
+    assert!(matches!(
+        validate_orderbook(&zero_qty),
+        Err(ValidationError::NonPositive {
+            side: Side::Bid,
+            index: 0,
+            ..
+        })
+    ));
+
+    let unsorted = book(&[("1.0", "1"), ("1.1", "1")], &[]);
+    assert!(matches!(
+        validate_orderbook(&unsorted),
+        Err(ValidationError::BidsNotDescending { index: 1, .. })
+    ));
+
+    let unsorted = book(&[], &[("1.1", "1"), ("1.1", "1")]);
+    assert!(matches!(
+        validate_orderbook(&unsorted),
+        Err(ValidationError::AsksNotAscending { index: 1, .. })
+    ));
+
+    let locked = book(&[("1.0", "1")], &[("1.0", "1")]);
+    assert!(matches!(
+        validate_orderbook(&locked),
+        Err(ValidationError::Crossed { .. })
+    ));
+}
+
+#[test]
+fn diff_finds_added_changed_and_removed() {
+    let prev = sample();
+    let curr = book(
+        &[("1.0000", "12"), ("0.9990", "20"), ("0.9985", "7")],
+        &[("1.0010", "5"), ("1.0020", "0"), ("1.0030", "25")],
+    );
+    let delta = diff_orderbooks(&prev, &curr);
+    assert_eq!(delta.bids.changed, [level("1.0000", "12")]);
+    assert_eq!(delta.bids.added, [level("0.9985", "7")]);
+    assert_eq!(delta.bids.removed, [decimal("0.9980")]);
+    // Zero quantity means removed
+    assert!(delta.asks.added.is_empty() && delta.asks.changed.is_empty());
+    assert_eq!(delta.asks.removed, [decimal("1.0020")]);
+}
+
+#[test]
+fn diff_of_identical_books_is_empty() {
+    let delta = diff_orderbooks(&sample(), &sample());
+    assert!(delta.bids.is_empty() && delta.asks.is_empty());
+}
+
+fn event(first: u64, last: u64, bids: &[(&str, &str)]) -> DepthEvent {
+    DepthEvent {
+        first_update_id: first,
+        final_update_id: last,
+        bids: bids.iter().map(|(p, q)| level(p, q)).collect(),
+        asks: Vec::new(),
+    }
+}
+
+#[test]
+fn local_book_applies_events_after_the_snapshot() {
+    // sample() is at update 1
+    let mut local = LocalBook::from_snapshot(sample());
+    assert_eq!(local.apply(event(0, 1, &[("1.0000", "99")])), Ok(false));
+    // The first event may start before the snapshot, as long as it covers update 2
+    assert_eq!(
+        local.apply(event(1, 3, &[("1.0000", "0"), ("1.0005", "4")])),
+        Ok(true)
+    );
+    assert_eq!(local.apply(event(4, 4, &[("0.9990", "21")])), Ok(true));
+    assert_eq!(local.last_update_id(), 4);
+    let top = local.to_orderbook(2);
+    assert_eq!(top.bids, [level("1.0005", "4"), level("0.9990", "21")]);
+    assert_eq!(top.asks, [level("1.0010", "5"), level("1.0020", "15")]);
+}
+
+#[test]
+fn local_book_rejects_a_gap() {
+    let mut local = LocalBook::from_snapshot(sample());
+    assert_eq!(local.apply(event(2, 2, &[])), Ok(true));
+    assert!(local.apply(event(4, 5, &[])).is_err());
+}
@@ -0,0 +1,416 @@
+// BinanceClient against a local mock server serving canned depth and ticker JSON
+
+use binance_price_checker::{
+    http_client, normalize_symbol, symbol_url, weight_limit, ApiError, BinanceClient,
+    BinanceResponse, Kline, Level, OrderBook, SymbolFilters,
+};
+use reqwest::StatusCode;
+use rust_decimal::Decimal;
+use std::error::Error;
+use std::str::FromStr;
+use std::time::Duration;
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const DEPTH: &str = r#"{
+    "lastUpdateId": 1027024,
+    "bids": [["1.23450000", "100.00000000"], ["1.23440000", "250.50000000"]],
+    "asks": [["1.23460000", "80.00000000"], ["1.23470000", "12.10000000"]]
+}"#;
+
+fn client(server: &MockServer) -> BinanceClient {
+    let http = http_client(Duration::from_secs(5), Duration::from_secs(5), None).unwrap();
+    // Errors are asserted on directly; keep the retry messages quiet
+    BinanceClient::new(http, &server.uri()).on_retry(|_| {})
+}
+
+fn json(body: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_raw(body.to_string(), "application/json")
+}
+
+fn decimal(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+}
+
+fn api_error<'a>(error: &'a (dyn Error + 'static)) -> &'a ApiError {
+    error
+        .downcast_ref::<ApiError>()
+        .unwrap_or_else(|| panic!("expected an ApiError, got {:?}", error))
+}
+
+async fn depth_mock(server: &MockServer, response: ResponseTemplate) {
+    Mock::given(method("GET"))
+        .and(path("/api/v3/depth"))
+        .and(query_param("symbol", "SUIUSDT"))
+        .and(query_param("limit", "5"))
+        .respond_with(response)
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn orderbook_snapshot_keeps_every_digit() {
+    let server = MockServer::start().await;
+    depth_mock(&server, json(DEPTH)).await;
+
+    let (book, cached) = client(&server)
+        .get_orderbook_snapshot("SUIUSDT", 5)
+        .await
+        .unwrap();
+    assert!(!cached);
+    assert_eq!(book.last_update_id, 1027024);
+    assert_eq!(book.bids.len(), 2);
+    assert_eq!(
+        book.asks[1],
+        Level {
+            price: decimal("1.2347"),
+            qty: decimal("12.1"),
+        }
+    );
+    // Written back exactly as Binance sent it
+    assert_eq!(book.bids[1].to_strings(), ["1.23440000", "250.50000000"]);
+}
+
+#[tokio::test]
+async fn orderbook_snapshot_rejects_malformed_level() {
+    let server = MockServer::start().await;
+    let body = r#"{"lastUpdateId": 1, "bids": [["abc", "1.0"]], "asks": []}"#;
+    depth_mock(&server, json(body)).await;
+
+    let error = client(&server)
+        .get_orderbook_snapshot("SUIUSDT", 5)
+        .await
+        .unwrap_err();
+    match api_error(error.as_ref()) {
+        ApiError::Parse { body: kept, .. } => assert_eq!(kept, body),
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn orderbook_snapshot_rejects_truncated_body() {
+    let server = MockServer::start().await;
+    depth_mock(&server, json(&DEPTH[..40])).await;
+
+    let error = client(&server)
+        .get_orderbook_snapshot("SUIUSDT", 5)
+        .await
+        .unwrap_err();
+    assert!(matches!(api_error(error.as_ref()), ApiError::Parse { .. }));
+}
+
+#[tokio::test]
+async fn client_error_is_not_retried() {
+    let server = MockServer::start().await;
+    Mock::given(path("/api/v3/depth"))
+        .respond_with(ResponseTemplate::new(400))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let error = client(&server)
+        .get_orderbook_snapshot("SUIUSDT", 5)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        api_error(error.as_ref()),
+        ApiError::Http {
+            status: StatusCode::BAD_REQUEST,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn server_error_is_retried() {
+    let server = MockServer::start().await;
+    Mock::given(path("/api/v3/depth"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    depth_mock(&server, json(DEPTH)).await;
+
+    let (book, _) = client(&server)
+        .get_orderbook_snapshot("SUIUSDT", 5)
+        .await
+        .unwrap();
+    assert_eq!(book.asks.len(), 2);
+}
+
+#[tokio::test]
+async fn rate_limit_is_returned_and_recorded_as_a_ban() {
+    let server = MockServer::start().await;
+    Mock::given(path("/api/v3/depth"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "120"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = client(&server);
+    let error = client
+        .get_orderbook_snapshot("SUIUSDT", 5)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        api_error(error.as_ref()),
+        ApiError::RateLimited {
+            retry_after: Some(_),
+            ..
+        }
+    ));
+    // Waiting out the ban is left to the caller
+    let delay = client.weight().throttle_delay(1200, 0.9).unwrap();
+    assert!(delay > Duration::from_secs(100), "{:?}", delay);
+}
+
+#[tokio::test]
+async fn region_block_is_reported() {
+    let server = MockServer::start().await;
+    Mock::given(path("/api/v3/ticker/price"))
+        .respond_with(ResponseTemplate::new(451))
+        .mount(&server)
+        .await;
+
+    let error = client(&server)
+        .get_current_price("SUIUSDT")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        api_error(error.as_ref()),
+        ApiError::RegionBlocked { .. }
+    ));
+}
+
+#[tokio::test]
+async fn unchanged_book_comes_from_the_cache() {
+    let server = MockServer::start().await;
+    Mock::given(path("/api/v3/depth"))
+        .and(header("if-none-match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304))
+        .mount(&server)
+        .await;
+    depth_mock(&server, json(DEPTH).insert_header("etag", "\"v1\"")).await;
+
+    let client = client(&server).conditional_depth(true);
+    let (first, cached) = client.get_orderbook_snapshot("SUIUSDT", 5).await.unwrap();
+    assert!(!cached);
+    let (second, cached) = client.get_orderbook_snapshot("SUIUSDT", 5).await.unwrap();
+    assert!(cached);
+    assert_eq!(second.bids, first.bids);
+}
+
+#[tokio::test]
+async fn used_weight_is_recorded() {
+    let server = MockServer::start().await;
+    depth_mock(
+        &server,
+        json(DEPTH).insert_header("x-mbx-used-weight-1m", "42"),
+    )
+    .await;
+
+    let client = client(&server);
+    client.get_orderbook_snapshot("SUIUSDT", 5).await.unwrap();
+    assert_eq!(client.weight().used(), 42);
+}
+
+#[tokio::test]
+async fn current_price_is_read() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v3/ticker/price"))
+        .and(query_param("symbol", "SUIUSDT"))
+        .respond_with(json(r#"{"symbol": "SUIUSDT", "price": "1.23450000"}"#))
+        .mount(&server)
+        .await;
+
+    let price = client(&server).get_current_price("SUIUSDT").await.unwrap();
+    assert_eq!(price.price, "1.23450000");
+    assert!(price.timestamp > 0);
+}
+
+#[tokio::test]
+async fn current_price_without_price_field_fails() {
+    let server = MockServer::start().await;
+    Mock::given(path("/api/v3/ticker/price"))
+        .respond_with(json(r#"{"symbol": "SUIUSDT"}"#))
+        .mount(&server)
+        .await;
+
+    let error = client(&server)
+        .get_current_price("SUIUSDT")
+        .await
+        .unwrap_err();
+    assert_eq!(error.to_string(), "Failed to extract price");
+}
+
+#[tokio::test]
+async fn current_price_rejects_non_json() {
+    let server = MockServer::start().await;
+    Mock::given(path("/api/v3/ticker/price"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html>maintenance</html>"))
+        .mount(&server)
+        .await;
+
+    let error = client(&server)
+        .get_current_price("SUIUSDT")
+        .await
+        .unwrap_err();
+    assert!(matches!(api_error(error.as_ref()), ApiError::Parse { .. }));
+}
+
+#[tokio::test]
+async fn error_body_with_200_is_a_binance_error() {
+    let server = MockServer::start().await;
+    let body = r#"{"code": -1121, "msg": "Invalid symbol."}"#;
+    Mock::given(path("/api/v3/ticker/price"))
+        .respond_with(json(body))
+        .mount(&server)
+        .await;
+    depth_mock(&server, json(body)).await;
+
+    let client = client(&server);
+    let price_error = client.get_current_price("SUIUSDT").await.unwrap_err();
+    let depth_error = client
+        .get_orderbook_snapshot("SUIUSDT", 5)
+        .await
+        .unwrap_err();
+    for error in [price_error, depth_error] {
+        match api_error(error.as_ref()) {
+            ApiError::Binance { code, msg, .. } => {
+                assert_eq!(*code, -1121);
+                assert_eq!(msg, "Invalid symbol.");
+            }
+            other => panic!("expected a Binance error, got {:?}", other),
+        }
+        assert!(error.to_string().ends_with("Invalid symbol."), "{}", error);
+    }
+}
+
+#[tokio::test]
+async fn ticker_24h_is_read() {
+    let server = MockServer::start().await;
+    let body = r#"{
+        "symbol": "SUIUSDT",
+        "priceChangePercent": "-2.150",
+        "highPrice": "1.30000000",
+        "lowPrice": "1.20000000",
+        "volume": "1500000.00000000",
+        "quoteVolume": "1880000.00000000"
+    }"#;
+    Mock::given(path("/api/v3/ticker/24hr"))
+        .respond_with(json(body))
+        .mount(&server)
+        .await;
+
+    let ticker = client(&server).get_24h_ticker("SUIUSDT").await.unwrap();
+    assert_eq!(ticker.price_change_percent, decimal("-2.15"));
+    assert_eq!(ticker.high_price, decimal("1.3"));
+    assert_eq!(ticker.quote_volume, decimal("1880000"));
+}
+
+#[tokio::test]
+async fn klines_are_read_from_rows() {
+    let server = MockServer::start().await;
+    let body = r#"[
+        [1700000000000, "1.0000", "1.2000", "0.9000", "1.1000", "1500.5",
+         1700000059999, "1650.55", 42, "700.25", "770.27", "0"],
+        [1700000060000, "1.1000", "1.1000", "1.1000", "1.1000", "0",
+         1700000119999, "0", 0, "0", "0", "0"]
+    ]"#;
+    Mock::given(method("GET"))
+        .and(path("/api/v3/klines"))
+        .and(query_param("symbol", "SUIUSDT"))
+        .and(query_param("interval", "1m"))
+        .and(query_param("startTime", "1700000000000"))
+        .and(query_param("limit", "1000"))
+        .respond_with(json(body))
+        .mount(&server)
+        .await;
+
+    // Capped at KLINE_LIMIT
+    let klines = client(&server)
+        .get_klines("SUIUSDT", "1m", Some(1700000000000), None, 5000)
+        .await
+        .unwrap();
+    assert_eq!(klines.len(), 2);
+    assert_eq!(
+        klines[0],
+        Kline {
+            open_time: 1700000000000,
+            open: decimal("1"),
+            high: decimal("1.2"),
+            low: decimal("0.9"),
+            close: decimal("1.1"),
+            volume: decimal("1500.5"),
+            close_time: 1700000059999,
+            quote_volume: decimal("1650.55"),
+            trades: 42,
+            taker_buy_volume: decimal("700.25"),
+            taker_buy_quote_volume: decimal("770.27"),
+        }
+    );
+    let written = serde_json::to_value(&klines[1]).unwrap();
+    assert_eq!(written["openTime"], 1700000060000u64);
+    assert_eq!(written["takerBuyQuoteVolume"], "0");
+}
+
+// Keep the fixture itself honest: it's a valid book
+#[test]
+fn depth_fixture_parses() {
+    let book: OrderBook = serde_json::from_str(DEPTH).unwrap();
+    assert!(binance_price_checker::validate_orderbook(&book).is_ok());
+}
+
+#[test]
+fn binance_response_prefers_the_error_shape() {
+    let parsed: BinanceResponse<serde_json::Value> =
+        BinanceResponse::from_body(r#"{"code": -1003, "msg": "Too many requests."}"#).unwrap();
+    assert!(matches!(parsed, BinanceResponse::Error { code: -1003, .. }));
+
+    let parsed: BinanceResponse<serde_json::Value> =
+        BinanceResponse::from_body(r#"{"price": "1.0"}"#).unwrap();
+    assert!(matches!(parsed, BinanceResponse::Ok(_)));
+}
+
+#[test]
+fn weight_limit_is_the_per_minute_request_weight() {
+    let info = serde_json::json!({"rateLimits": [
+        {"rateLimitType": "REQUEST_WEIGHT", "interval": "SECOND", "intervalNum": 10, "limit": 300},
+        {"rateLimitType": "ORDERS", "interval": "MINUTE", "intervalNum": 1, "limit": 100},
+        {"rateLimitType": "REQUEST_WEIGHT", "interval": "MINUTE", "intervalNum": 1, "limit": 6000}
+    ]});
+    assert_eq!(weight_limit(&info), Ok(6000));
+    assert!(weight_limit(&serde_json::json!({"rateLimits": []})).is_err());
+}
+
+#[test]
+fn symbol_filters_read_tick_and_step_size() {
+    let info = serde_json::json!({"symbols": [{"symbol": "SUIUSDT", "filters": [
+        {"filterType": "PRICE_FILTER", "minPrice": "0.00010000", "tickSize": "0.00010000"},
+        {"filterType": "LOT_SIZE", "minQty": "0.10000000", "stepSize": "0.10000000"}
+    ]}]});
+    let filters = SymbolFilters::parse(&info, "SUIUSDT").unwrap();
+    assert_eq!(filters.tick_size, decimal("0.0001"));
+    assert_eq!((filters.price_decimals(), filters.qty_decimals()), (4, 1));
+    assert!(SymbolFilters::parse(&info, "BTCUSDT").is_err());
+}
+
+#[test]
+fn symbols_are_normalized_into_urls() {
+    assert_eq!(normalize_symbol(" suiusdt ").unwrap(), "SUIUSDT");
+    assert!(normalize_symbol("SUI/USDT").unwrap_err().contains("'/'"));
+    assert!(normalize_symbol("").is_err());
+    assert_eq!(
+        symbol_url(
+            "https://api.binance.com",
+            "/api/v3/depth",
+            "btcusdt",
+            "&limit=5"
+        )
+        .unwrap(),
+        "https://api.binance.com/api/v3/depth?symbol=BTCUSDT&limit=5"
+    );
+    assert!(symbol_url("https://api.binance.com", "/api/v3/depth", "BTC-USDT", "").is_err());
+}